@@ -21,10 +21,33 @@ impl<'a> AliasesResolver<'a> {
             Reference::Alias(alias) => {
                 if let Some(result) = self.known_aliases.get(alias) {
                     result.clone()
+                } else if let Some(result) =
+                    self.read_able_transaction.get_thesis_id_by_alias(alias)?
+                {
+                    result
                 } else {
-                    self.read_able_transaction
-                        .get_thesis_id_by_alias(alias)?
-                        .ok_or_else(|| anyhow!("Can not find thesis id by alias {alias:?}"))?
+                    return Err(anyhow!("Can not find thesis id by alias {alias:?}"));
+                }
+            }
+            Reference::IdPrefix(prefix) => {
+                let mut candidates = self
+                    .read_able_transaction
+                    .get_theses_ids_by_prefix(prefix)?;
+                match candidates.len() {
+                    0 => {
+                        return Err(anyhow!("Can not find thesis id by id prefix {prefix:?}"));
+                    }
+                    1 => candidates.pop().unwrap(),
+                    _ => {
+                        return Err(anyhow!(
+                            "Thesis id prefix {prefix:?} is ambiguous, candidates are {}",
+                            candidates
+                                .iter()
+                                .map(|candidate| candidate.to_string())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ));
+                    }
                 }
             }
         })
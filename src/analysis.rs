@@ -0,0 +1,526 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{anyhow, Result};
+use fallible_iterator::FallibleIterator;
+use trove::ObjectId;
+
+use crate::alias::Alias;
+use crate::aliases_resolver::AliasesResolver;
+use crate::commands::{Command, CommandsIterator, Reference};
+use crate::content::Content;
+use crate::paragraphs::Paragraph;
+use crate::read_transaction::ReadTransactionMethods;
+use crate::text::Text;
+use crate::thesis::Thesis;
+
+// lines are counted from zero, characters are byte offsets in line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Range {
+    fn in_line(line: usize, start: usize, end: usize) -> Self {
+        Self {
+            start: Position {
+                line,
+                character: start,
+            },
+            end: Position {
+                line,
+                character: end,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub range: Range,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CompletionKind {
+    Alias,
+    RelationKind,
+    Tag,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Completion {
+    pub kind: CompletionKind,
+    pub label: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub range: Range,
+    pub new_text: String,
+}
+
+// role of paragraph line, determined the same way CommandsIterator does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineRole {
+    FirstLine,
+    Reference,
+    RelationKind,
+    Tag,
+    Text,
+    Other,
+}
+
+// commands document parsed against sweater, which answers editor queries
+pub struct Analysis<'a> {
+    read_able_transaction: &'a dyn ReadTransactionMethods<'a>,
+    // paragraphs are kept with namespaces in which they are
+    paragraphs: Vec<(Paragraph, Option<String>)>,
+    diagnostics: Vec<Diagnostic>,
+    declarations: BTreeMap<Alias, (Range, ObjectId)>,
+    theses: BTreeMap<ObjectId, (Range, Thesis)>,
+}
+
+impl<'a> Analysis<'a> {
+    pub fn new(
+        input: &str,
+        read_able_transaction: &'a dyn ReadTransactionMethods<'a>,
+    ) -> Result<Self> {
+        let mut result = Self {
+            read_able_transaction,
+            paragraphs: Vec::new(),
+            diagnostics: Vec::new(),
+            declarations: BTreeMap::new(),
+            theses: BTreeMap::new(),
+        };
+        let mut aliases_resolver = AliasesResolver {
+            read_able_transaction,
+            known_aliases: BTreeMap::new(),
+        };
+        let mut commands_iterator = CommandsIterator::new(
+            input,
+            &read_able_transaction
+                .sweater_config()
+                .supported_relations_kinds,
+            &mut aliases_resolver,
+        );
+        while let Some((paragraph, commands)) = commands_iterator.next_paragraph_commands()? {
+            let first_line = Self::first_line_range(&paragraph);
+            let alias_range = Self::alias_range(&paragraph).unwrap_or(first_line);
+            match commands {
+                Ok(commands) => {
+                    for command in commands {
+                        match command {
+                            Command::AddThesis(thesis) => {
+                                let thesis_id = read_able_transaction.thesis_id(&thesis)?;
+                                if let Some(ref alias) = thesis.alias {
+                                    result
+                                        .declarations
+                                        .insert(alias.clone(), (alias_range, thesis_id.clone()));
+                                }
+                                result.theses.insert(thesis_id, (first_line, thesis));
+                            }
+                            Command::SetAlias(thesis_id, alias) => {
+                                result.declarations.insert(alias, (alias_range, thesis_id));
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                // includes can not be followed without file, so they are not reported
+                Err(_) if paragraph.lines[0].trim_start().starts_with("include ") => {}
+                Err(error) => result.diagnostics.push(Diagnostic {
+                    range: Range {
+                        start: first_line.start,
+                        end: Position {
+                            line: paragraph.lines_numbers.last().unwrap() - 1,
+                            character: paragraph.lines.last().unwrap().len(),
+                        },
+                    },
+                    message: format!("{error:#}"),
+                }),
+            }
+            result.paragraphs.push((
+                paragraph,
+                commands_iterator
+                    .namespace()
+                    .map(|namespace| namespace.to_string()),
+            ));
+        }
+        Ok(result)
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    fn first_line_range(paragraph: &Paragraph) -> Range {
+        Range::in_line(paragraph.lines_numbers[0] - 1, 0, paragraph.lines[0].len())
+    }
+
+    fn alias_range(paragraph: &Paragraph) -> Option<Range> {
+        CommandsIterator::command_first_line_regex()
+            .captures(&paragraph.lines[0])
+            .and_then(|captures| captures.get(3))
+            .map(|alias_match| {
+                Range::in_line(
+                    paragraph.lines_numbers[0] - 1,
+                    alias_match.start(),
+                    alias_match.end(),
+                )
+            })
+    }
+
+    fn line_role(&self, paragraph: &Paragraph, line_index: usize) -> LineRole {
+        if line_index == 0 {
+            return LineRole::FirstLine;
+        }
+        let Some(captures) =
+            CommandsIterator::command_first_line_regex().captures(&paragraph.lines[0])
+        else {
+            return LineRole::Other;
+        };
+        let sweater_config = self.read_able_transaction.sweater_config();
+        let is_relation_kind = |line_index: usize| {
+            paragraph
+                .lines
+                .get(line_index)
+                .is_some_and(|line| sweater_config.relation_kind(line).is_some())
+        };
+        // paragraph looking like relation of unsupported kind is shown as relation
+        let is_relation = &captures[1] == "+"
+            && CommandsIterator::is_relation_lines(
+                &paragraph
+                    .lines
+                    .iter()
+                    .map(|line| line.as_str())
+                    .collect::<Vec<_>>(),
+                |line| sweater_config.relation_kind(line).is_some(),
+            )
+            .unwrap_or(true);
+        match (&captures[1], line_index) {
+            ("+", _) if is_relation && is_relation_kind(line_index) => LineRole::RelationKind,
+            ("+", _) if is_relation => LineRole::Reference,
+            ("+", _) => LineRole::Text,
+            ("-" | "#" | "^" | "@", 1) => LineRole::Reference,
+            ("#" | "^", _) => LineRole::Tag,
+            _ => LineRole::Other,
+        }
+    }
+
+    // paragraph and index of it's line at given position, index is lines count if position is
+    // on the line right after paragraph, so that completions work for line being typed
+    fn line_at(&self, position: Position) -> Option<(&Paragraph, Option<&str>, usize)> {
+        self.paragraphs.iter().find_map(|(paragraph, namespace)| {
+            paragraph
+                .lines_numbers
+                .iter()
+                .position(|line_number| line_number - 1 == position.line)
+                .or_else(|| {
+                    (*paragraph.lines_numbers.last().unwrap() == position.line)
+                        .then_some(paragraph.lines.len())
+                })
+                .map(|line_index| (paragraph, namespace.as_deref(), line_index))
+        })
+    }
+
+    // alias or thesis id under cursor, either declared on first line or referenced, with alias
+    // expanded in paragraph namespace
+    fn token_at(&self, position: Position) -> Option<(Range, String)> {
+        let (paragraph, namespace, line_index) = self.line_at(position)?;
+        let line = paragraph.lines.get(line_index)?;
+        let contains = |start: usize, end: usize| (start..=end).contains(&position.character);
+        let token = match self.line_role(paragraph, line_index) {
+            LineRole::FirstLine => Self::alias_range(paragraph)
+                .filter(|range| contains(range.start.character, range.end.character))
+                .map(|range| {
+                    (
+                        range,
+                        line[range.start.character..range.end.character].to_string(),
+                    )
+                }),
+            LineRole::Reference => contains(0, line.len()).then(|| {
+                (
+                    Range::in_line(position.line, 0, line.len()),
+                    line.to_string(),
+                )
+            }),
+            LineRole::Text => Text::reference_in_text_regex()
+                .captures_iter(line)
+                .filter_map(|captures| captures.get(3).or(captures.get(4)))
+                .find(|reference_match| contains(reference_match.start(), reference_match.end()))
+                .map(|reference_match| {
+                    (
+                        Range::in_line(
+                            position.line,
+                            reference_match.start(),
+                            reference_match.end(),
+                        ),
+                        reference_match.as_str().to_string(),
+                    )
+                }),
+            _ => None,
+        };
+        token.map(|(range, token)| (range, CommandsIterator::expanded_alias(namespace, &token)))
+    }
+
+    fn resolve(&self, reference: &str) -> Option<ObjectId> {
+        match Reference::new(reference).ok()? {
+            Reference::Alias(alias) => {
+                if let Some((_, thesis_id)) = self.declarations.get(&alias) {
+                    return Some(thesis_id.clone());
+                }
+                AliasesResolver {
+                    read_able_transaction: self.read_able_transaction,
+                    known_aliases: BTreeMap::new(),
+                }
+                .get_thesis_id_by_reference(&Reference::Alias(alias))
+                .ok()
+            }
+            Reference::ObjectId(thesis_id) => (self.theses.contains_key(&thesis_id)
+                || matches!(
+                    self.read_able_transaction.get_thesis(&thesis_id),
+                    Ok(Some(_))
+                ))
+            .then_some(thesis_id),
+            reference @ Reference::IdPrefix(_) => AliasesResolver {
+                read_able_transaction: self.read_able_transaction,
+                known_aliases: BTreeMap::new(),
+            }
+            .get_thesis_id_by_reference(&reference)
+            .ok(),
+        }
+    }
+
+    pub fn definition(&self, position: Position) -> Option<Range> {
+        let (_, reference) = self.token_at(position)?;
+        if let Some((range, _)) = self.declarations.get(&Alias(reference.clone())) {
+            return Some(*range);
+        }
+        self.theses
+            .get(&self.resolve(&reference)?)
+            .map(|(range, _)| *range)
+    }
+
+    fn thesis_name(&self, thesis_id: &ObjectId) -> Result<String> {
+        if let Some((alias, _)) = self
+            .declarations
+            .iter()
+            .find(|(_, (_, declared_thesis_id))| declared_thesis_id == thesis_id)
+        {
+            return Ok(alias.0.clone());
+        }
+        Ok(
+            match self
+                .read_able_transaction
+                .get_alias_by_thesis_id(thesis_id)?
+            {
+                Some(alias) => alias.0,
+                None => format!(
+                    "~{}",
+                    self.read_able_transaction
+                        .get_shortest_unambiguous_prefix(thesis_id)?
+                ),
+            },
+        )
+    }
+
+    pub fn hover(&self, position: Position) -> Result<Option<String>> {
+        let Some(thesis_id) = self
+            .token_at(position)
+            .and_then(|(_, reference)| self.resolve(&reference))
+        else {
+            return Ok(None);
+        };
+        let thesis = match self.theses.get(&thesis_id) {
+            Some((_, thesis)) => thesis.clone(),
+            None => match self.read_able_transaction.get_thesis(&thesis_id)? {
+                Some(thesis) => thesis,
+                None => return Ok(None),
+            },
+        };
+        let mut result = match thesis.content {
+            Content::Text(ref text) => text.composed_with_aliases(self.read_able_transaction)?,
+            Content::Relation(ref relation) => format!(
+                "{} {} {}",
+                self.thesis_name(&relation.from)?,
+                relation.kind.0,
+                self.thesis_name(&relation.to)?
+            ),
+        };
+        if !thesis.tags.is_empty() {
+            result.push_str("\n\n");
+            result.push_str(
+                &thesis
+                    .tags
+                    .iter()
+                    .map(|tag| format!("#{}", tag.0))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            );
+        }
+        Ok(Some(result))
+    }
+
+    pub fn completions(&self, position: Position) -> Result<Vec<Completion>> {
+        let Some((paragraph, _, line_index)) = self.line_at(position) else {
+            return Ok(Vec::new());
+        };
+        let line_before_cursor = paragraph
+            .lines
+            .get(line_index)
+            .map(|line| &line[..position.character.min(line.len())])
+            .unwrap_or("");
+        let kind = match self.line_role(paragraph, line_index) {
+            LineRole::FirstLine
+                if line_before_cursor
+                    .split(' ')
+                    .next_back()
+                    .is_some_and(|word| word.starts_with('#')) =>
+            {
+                CompletionKind::Tag
+            }
+            LineRole::Text if line_before_cursor.rfind('[') > line_before_cursor.rfind(']') => {
+                CompletionKind::Alias
+            }
+            LineRole::Reference => CompletionKind::Alias,
+            LineRole::Text if line_index == 1 || line_index == 3 => CompletionKind::Alias,
+            LineRole::RelationKind => CompletionKind::RelationKind,
+            LineRole::Text if line_index == 2 => CompletionKind::RelationKind,
+            LineRole::Tag => CompletionKind::Tag,
+            _ => return Ok(Vec::new()),
+        };
+
+        let mut labels = BTreeSet::new();
+        match kind {
+            CompletionKind::Alias => {
+                labels.extend(self.declarations.keys().map(|alias| alias.0.clone()));
+                let mut theses = self.read_able_transaction.iter_theses()?;
+                while let Some(thesis) = theses.next()? {
+                    labels.extend(thesis.alias.map(|alias| alias.0));
+                }
+            }
+            CompletionKind::RelationKind => {
+                let sweater_config = self.read_able_transaction.sweater_config();
+                labels.extend(
+                    sweater_config
+                        .supported_relations_kinds
+                        .iter()
+                        .map(|relation_kind| relation_kind.0.clone()),
+                );
+                labels.extend(
+                    sweater_config
+                        .relations_kinds_shorthands
+                        .values()
+                        .flatten()
+                        .cloned(),
+                );
+            }
+            CompletionKind::Tag => {
+                for (_, thesis) in self.theses.values() {
+                    labels.extend(thesis.tags.iter().map(|tag| tag.0.clone()));
+                }
+                let mut theses = self.read_able_transaction.iter_theses()?;
+                while let Some(thesis) = theses.next()? {
+                    labels.extend(thesis.tags.into_iter().map(|tag| tag.0));
+                }
+            }
+        }
+        Ok(labels
+            .into_iter()
+            .map(|label| Completion { kind, label })
+            .collect())
+    }
+
+    // alias written relatively to namespace stays relative if new alias is in the same namespace
+    fn written_alias(namespace: Option<&str>, old_written_alias: &str, new_alias: &str) -> String {
+        if let Some(namespace) = namespace
+            && old_written_alias.starts_with('.')
+            && let Some(relative_alias) = new_alias.strip_prefix(namespace)
+            && relative_alias.starts_with('.')
+        {
+            relative_alias.to_string()
+        } else {
+            new_alias.to_string()
+        }
+    }
+
+    // renames alias in this document only, alias stored in sweater is changed when document
+    // setting new alias is applied
+    pub fn rename(&self, position: Position, new_alias: &str) -> Result<Vec<Edit>> {
+        let (_, old_alias) = self
+            .token_at(position)
+            .ok_or_else(|| anyhow!("Can not find alias to rename at {position:?}"))?;
+        if !matches!(Reference::new(&old_alias), Ok(Reference::Alias(_)))
+            || self.resolve(&old_alias).is_none()
+        {
+            return Err(anyhow!(
+                "Can not rename {old_alias:?} as it is not alias of any thesis"
+            ));
+        }
+        // alias declared only in sweater would stay there, so renaming it's references in
+        // document would break them
+        if !self.declarations.contains_key(&Alias(old_alias.clone())) {
+            return Err(anyhow!(
+                "Can not rename alias {old_alias:?} as it is not declared in document, declare it \
+                 with '@' paragraph to rename it"
+            ));
+        }
+        let namespace = self
+            .line_at(position)
+            .and_then(|(_, namespace, _)| namespace);
+        let new_alias = Alias(CommandsIterator::expanded_alias(namespace, new_alias));
+        new_alias.validated()?;
+        if self.resolve(&new_alias.0).is_some() {
+            return Err(anyhow!(
+                "Can not rename alias {old_alias:?} to {:?} as it is already used",
+                new_alias.0
+            ));
+        }
+
+        let mut result = Vec::new();
+        for (paragraph, namespace) in self.paragraphs.iter() {
+            let namespace = namespace.as_deref();
+            let is_old_alias =
+                |alias: &str| CommandsIterator::expanded_alias(namespace, alias) == old_alias;
+            for (line_index, line) in paragraph.lines.iter().enumerate() {
+                let line_number = paragraph.lines_numbers[line_index] - 1;
+                let ranges: Vec<Range> = match self.line_role(paragraph, line_index) {
+                    LineRole::FirstLine => Self::alias_range(paragraph)
+                        .filter(|range| {
+                            is_old_alias(&line[range.start.character..range.end.character])
+                        })
+                        .into_iter()
+                        .collect(),
+                    LineRole::Reference if is_old_alias(line) => {
+                        vec![Range::in_line(line_number, 0, line.len())]
+                    }
+                    LineRole::Text => Text::reference_in_text_regex()
+                        .captures_iter(line)
+                        .filter_map(|captures| captures.get(4))
+                        .filter(|alias_match| is_old_alias(alias_match.as_str()))
+                        .map(|alias_match| {
+                            Range::in_line(line_number, alias_match.start(), alias_match.end())
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                result.extend(ranges.into_iter().map(|range| Edit {
+                    range,
+                    new_text: Self::written_alias(
+                        namespace,
+                        &line[range.start.character..range.end.character],
+                        &new_alias.0,
+                    ),
+                }));
+            }
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+
+use woollib::formatter::format;
+use woollib::sweater::{Sweater, SweaterConfig};
+
+const USAGE: &str = "Usage: woollib-fmt [--check] [--aliases SWEATER_CONFIG] FILE...";
+
+fn main() -> Result<()> {
+    let mut check = false;
+    let mut sweater_config_path: Option<PathBuf> = None;
+    let mut paths = Vec::new();
+    let mut arguments = std::env::args().skip(1);
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--check" => check = true,
+            "--aliases" => {
+                sweater_config_path = Some(PathBuf::from(arguments.next().ok_or_else(|| {
+                    anyhow!("Sweater config path expected after --aliases. {USAGE}")
+                })?))
+            }
+            _ if argument.starts_with("--") => {
+                return Err(anyhow!("Unknown option {argument:?}. {USAGE}"));
+            }
+            _ => paths.push(PathBuf::from(argument)),
+        }
+    }
+    if paths.is_empty() {
+        return Err(anyhow!("No files to format. {USAGE}"));
+    }
+
+    let sweater = match sweater_config_path {
+        Some(ref path) => Some(Sweater::new(
+            serde_saphyr::from_str::<SweaterConfig>(
+                &std::fs::read_to_string(path)
+                    .with_context(|| format!("Can not read sweater config {path:?}"))?,
+            )
+            .with_context(|| format!("Can not parse sweater config {path:?}"))?,
+        )?),
+        None => None,
+    };
+
+    let mut unformatted_paths = Vec::new();
+    for path in paths {
+        let input = std::fs::read_to_string(&path)
+            .with_context(|| format!("Can not read commands file {path:?}"))?;
+        let formatted = match sweater {
+            Some(ref sweater) => {
+                sweater.lock_all_writes_and_read(|transaction| format(&input, Some(&transaction)))
+            }
+            None => format(&input, None),
+        }
+        .with_context(|| format!("Can not format commands file {path:?}"))?;
+        if formatted != input {
+            if check {
+                println!("{}", path.display());
+                unformatted_paths.push(path);
+            } else {
+                std::fs::write(&path, formatted)
+                    .with_context(|| format!("Can not write commands file {path:?}"))?;
+            }
+        }
+    }
+    if !unformatted_paths.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use fallible_iterator::FallibleIterator;
+
+use woollib::aliases_resolver::AliasesResolver;
+use woollib::commands::CommandsIterator;
+use woollib::lints::{lint, LintsConfig};
+use woollib::sweater::{Sweater, SweaterConfig};
+
+const USAGE: &str = "Usage: woollib-lint [--lints LINTS_CONFIG] SWEATER_CONFIG [FILE...]";
+
+// prints lints as JSON lines, one lint per line, and exits with code 1 if there are any; without
+// files sweater theses are linted, otherwise theses added or changed by files commands
+fn main() -> Result<()> {
+    let mut lints_config_path: Option<PathBuf> = None;
+    let mut paths = Vec::new();
+    let mut arguments = std::env::args().skip(1);
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--lints" => {
+                lints_config_path =
+                    Some(PathBuf::from(arguments.next().ok_or_else(|| {
+                        anyhow!("Lints config path expected after --lints. {USAGE}")
+                    })?))
+            }
+            _ if argument.starts_with("--") => {
+                return Err(anyhow!("Unknown option {argument:?}. {USAGE}"));
+            }
+            _ => paths.push(PathBuf::from(argument)),
+        }
+    }
+    if paths.is_empty() {
+        return Err(anyhow!("No sweater config path given. {USAGE}"));
+    }
+    let sweater_config_path = paths.remove(0);
+
+    let lints_config = match lints_config_path {
+        Some(ref path) => serde_saphyr::from_str::<LintsConfig>(
+            &std::fs::read_to_string(path)
+                .with_context(|| format!("Can not read lints config {path:?}"))?,
+        )
+        .with_context(|| format!("Can not parse lints config {path:?}"))?,
+        None => LintsConfig::default(),
+    };
+    let sweater = Sweater::new(
+        serde_saphyr::from_str::<SweaterConfig>(
+            &std::fs::read_to_string(&sweater_config_path)
+                .with_context(|| format!("Can not read sweater config {sweater_config_path:?}"))?,
+        )
+        .with_context(|| format!("Can not parse sweater config {sweater_config_path:?}"))?,
+    )?;
+
+    let lints = sweater.lock_all_writes_and_read(|transaction| {
+        if paths.is_empty() {
+            return lint(&lints_config, &transaction, None);
+        }
+        let mut aliases_resolver = AliasesResolver {
+            read_able_transaction: &transaction,
+            known_aliases: BTreeMap::new(),
+        };
+        let mut commands = Vec::new();
+        for path in paths.iter() {
+            commands.extend(
+                CommandsIterator::from_file(
+                    path,
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut aliases_resolver,
+                )?
+                .collect::<Vec<_>>()
+                .with_context(|| format!("Can not parse commands file {path:?}"))?,
+            );
+        }
+        lint(&lints_config, &transaction, Some(&commands))
+    })?;
+    for lint in lints.iter() {
+        println!("{}", serde_json::to_string(lint)?);
+    }
+    if !lints.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,293 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{anyhow, Context, Result};
+use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, LogMessage,
+    Notification as _, PublishDiagnostics,
+};
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, Rename, Request as _};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, DiagnosticSeverity,
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, Location, LogMessageParams, MarkupContent, MarkupKind, MessageType,
+    OneOf, PublishDiagnosticsParams, RenameParams, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind, TextEdit, Uri, WorkspaceEdit,
+};
+
+use woollib::analysis::{self, Analysis, CompletionKind};
+use woollib::sweater::{Sweater, SweaterConfig};
+
+const USAGE: &str = "Usage: woollib-lsp SWEATER_CONFIG";
+
+// language server protocol counts characters in UTF-16 code units, while analysis counts bytes
+fn to_lsp_position(text: &str, position: analysis::Position) -> lsp_types::Position {
+    let line = text.lines().nth(position.line).unwrap_or("");
+    lsp_types::Position {
+        line: position.line as u32,
+        character: line[..position.character.min(line.len())]
+            .encode_utf16()
+            .count() as u32,
+    }
+}
+
+fn to_lsp_range(text: &str, range: analysis::Range) -> lsp_types::Range {
+    lsp_types::Range {
+        start: to_lsp_position(text, range.start),
+        end: to_lsp_position(text, range.end),
+    }
+}
+
+fn from_lsp_position(text: &str, position: lsp_types::Position) -> analysis::Position {
+    let line = text.lines().nth(position.line as usize).unwrap_or("");
+    let mut utf16_length = 0;
+    let character = line
+        .char_indices()
+        .find(|(_, character)| {
+            let reached = utf16_length >= position.character as usize;
+            utf16_length += character.len_utf16();
+            reached
+        })
+        .map(|(byte_index, _)| byte_index)
+        .unwrap_or(line.len());
+    analysis::Position {
+        line: position.line as usize,
+        character,
+    }
+}
+
+struct Server {
+    connection: Connection,
+    sweater: Sweater,
+    documents: BTreeMap<Uri, String>,
+}
+
+impl Server {
+    fn document(&self, uri: &Uri) -> Result<&String> {
+        self.documents
+            .get(uri)
+            .ok_or_else(|| anyhow!("Can not find opened document {:?}", uri.as_str()))
+    }
+
+    fn publish_diagnostics(&self, uri: Uri) -> Result<()> {
+        let text = self.document(&uri)?;
+        let diagnostics = self.sweater.lock_all_writes_and_read(|transaction| {
+            Ok(Analysis::new(text, &transaction)?
+                .diagnostics()
+                .iter()
+                .map(|diagnostic| lsp_types::Diagnostic {
+                    range: to_lsp_range(text, diagnostic.range),
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    source: Some("woollib".to_string()),
+                    message: diagnostic.message.clone(),
+                    ..Default::default()
+                })
+                .collect::<Vec<_>>())
+        })?;
+        self.connection
+            .sender
+            .send(Message::Notification(Notification::new(
+                PublishDiagnostics::METHOD.to_string(),
+                PublishDiagnosticsParams {
+                    uri,
+                    diagnostics,
+                    version: None,
+                },
+            )))?;
+        Ok(())
+    }
+
+    fn handle_notification(&mut self, notification: Notification) -> Result<()> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params =
+                    serde_json::from_value::<DidOpenTextDocumentParams>(notification.params)?;
+                self.documents
+                    .insert(params.text_document.uri.clone(), params.text_document.text);
+                self.publish_diagnostics(params.text_document.uri)?;
+            }
+            DidChangeTextDocument::METHOD => {
+                let mut params =
+                    serde_json::from_value::<DidChangeTextDocumentParams>(notification.params)?;
+                // full text synchronization is requested, so the last change is the whole text
+                if let Some(change) = params.content_changes.pop() {
+                    self.documents
+                        .insert(params.text_document.uri.clone(), change.text);
+                    self.publish_diagnostics(params.text_document.uri)?;
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params =
+                    serde_json::from_value::<DidCloseTextDocumentParams>(notification.params)?;
+                self.documents.remove(&params.text_document.uri);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // none if request method is not supported
+    fn respond(&self, request: Request) -> Result<Option<serde_json::Value>> {
+        Ok(Some(match request.method.as_str() {
+            GotoDefinition::METHOD => {
+                let params = serde_json::from_value::<GotoDefinitionParams>(request.params)?;
+                let uri = params.text_document_position_params.text_document.uri;
+                let text = self.document(&uri)?;
+                let position =
+                    from_lsp_position(text, params.text_document_position_params.position);
+                serde_json::to_value(self.sweater.lock_all_writes_and_read(|transaction| {
+                    Ok(Analysis::new(text, &transaction)?
+                        .definition(position)
+                        .map(|range| {
+                            GotoDefinitionResponse::Scalar(Location {
+                                uri: uri.clone(),
+                                range: to_lsp_range(text, range),
+                            })
+                        }))
+                })?)?
+            }
+            HoverRequest::METHOD => {
+                let params = serde_json::from_value::<HoverParams>(request.params)?;
+                let text =
+                    self.document(&params.text_document_position_params.text_document.uri)?;
+                let position =
+                    from_lsp_position(text, params.text_document_position_params.position);
+                serde_json::to_value(self.sweater.lock_all_writes_and_read(|transaction| {
+                    Ok(Analysis::new(text, &transaction)?
+                        .hover(position)?
+                        .map(|value| Hover {
+                            contents: HoverContents::Markup(MarkupContent {
+                                kind: MarkupKind::PlainText,
+                                value,
+                            }),
+                            range: None,
+                        }))
+                })?)?
+            }
+            Completion::METHOD => {
+                let params = serde_json::from_value::<CompletionParams>(request.params)?;
+                let text = self.document(&params.text_document_position.text_document.uri)?;
+                let position = from_lsp_position(text, params.text_document_position.position);
+                serde_json::to_value(self.sweater.lock_all_writes_and_read(|transaction| {
+                    Ok(Analysis::new(text, &transaction)?
+                        .completions(position)?
+                        .into_iter()
+                        .map(|completion| CompletionItem {
+                            label: completion.label,
+                            kind: Some(match completion.kind {
+                                CompletionKind::Alias => CompletionItemKind::REFERENCE,
+                                CompletionKind::RelationKind => CompletionItemKind::KEYWORD,
+                                CompletionKind::Tag => CompletionItemKind::VALUE,
+                            }),
+                            ..Default::default()
+                        })
+                        .collect::<Vec<_>>())
+                })?)?
+            }
+            Rename::METHOD => {
+                let params = serde_json::from_value::<RenameParams>(request.params)?;
+                let uri = params.text_document_position.text_document.uri;
+                let text = self.document(&uri)?;
+                let position = from_lsp_position(text, params.text_document_position.position);
+                let edits = self.sweater.lock_all_writes_and_read(|transaction| {
+                    Ok(Analysis::new(text, &transaction)?
+                        .rename(position, &params.new_name)?
+                        .into_iter()
+                        .map(|edit| TextEdit {
+                            range: to_lsp_range(text, edit.range),
+                            new_text: edit.new_text,
+                        })
+                        .collect::<Vec<_>>())
+                })?;
+                serde_json::to_value(WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri.clone(), edits)])),
+                    ..Default::default()
+                })?
+            }
+            _ => return Ok(None),
+        }))
+    }
+
+    fn serve(mut self) -> Result<()> {
+        while let Ok(message) = self.connection.receiver.recv() {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        break;
+                    }
+                    let id: RequestId = request.id.clone();
+                    let method = request.method.clone();
+                    let response = match self.respond(request) {
+                        Ok(Some(result)) => Response::new_ok(id, result),
+                        Ok(None) => Response::new_err(
+                            id,
+                            lsp_server::ErrorCode::MethodNotFound as i32,
+                            format!("Unsupported request method {method:?}"),
+                        ),
+                        Err(error) => Response::new_err(
+                            id,
+                            lsp_server::ErrorCode::RequestFailed as i32,
+                            format!("{error:#}"),
+                        ),
+                    };
+                    self.connection.sender.send(Message::Response(response))?;
+                }
+                Message::Notification(notification) => {
+                    let method = notification.method.clone();
+                    // client does not expect answer to notification, so error is only logged and
+                    // server keeps working
+                    if let Err(error) = self.handle_notification(notification) {
+                        self.connection
+                            .sender
+                            .send(Message::Notification(Notification::new(
+                                LogMessage::METHOD.to_string(),
+                                LogMessageParams {
+                                    typ: MessageType::ERROR,
+                                    message: format!(
+                                        "Can not handle notification {method:?}: {error:#}"
+                                    ),
+                                },
+                            )))?;
+                    }
+                }
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+fn main() -> Result<()> {
+    let sweater_config_path = std::env::args()
+        .nth(1)
+        .ok_or_else(|| anyhow!("No sweater config path given. {USAGE}"))?;
+    let sweater = Sweater::new(
+        serde_saphyr::from_str::<SweaterConfig>(
+            &std::fs::read_to_string(&sweater_config_path)
+                .with_context(|| format!("Can not read sweater config {sweater_config_path:?}"))?,
+        )
+        .with_context(|| format!("Can not parse sweater config {sweater_config_path:?}"))?,
+    )?;
+
+    let (connection, io_threads) = Connection::stdio();
+    connection.initialize(serde_json::to_value(ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["[".to_string(), "#".to_string()]),
+            ..Default::default()
+        }),
+        rename_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    })?)?;
+    Server {
+        connection,
+        sweater,
+        documents: BTreeMap::new(),
+    }
+    .serve()?;
+    io_threads.join()?;
+    Ok(())
+}
@@ -1,4 +1,6 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, VecDeque};
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
 
 use anyhow::{anyhow, Context, Error, Result};
 use fallible_iterator::FallibleIterator;
@@ -9,25 +11,48 @@ use trove::ObjectId;
 use crate::alias::Alias;
 use crate::aliases_resolver::AliasesResolver;
 use crate::content::Content;
+use crate::paragraphs::{FilesParagraphsIterator, ReaderParagraphsIterator};
+// paragraphs types moved to paragraphs module, they are still available here
+pub use crate::paragraphs::{Paragraph, ParagraphsIterator};
+use crate::read_transaction::MINIMAL_ID_PREFIX_LENGTH;
 use crate::relation::{Relation, RelationKind};
 use crate::tag::Tag;
-use crate::text::Text;
+use crate::text::{Text, TextCharset};
 use crate::thesis::Thesis;
+use crate::write_transaction::ApplyMode;
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Reference {
     Alias(Alias),
     ObjectId(ObjectId),
+    IdPrefix(String),
 }
 
 impl Reference {
+    // full thesis id is parsed as id, ~ followed by thesis id prefix is parsed as id prefix,
+    // anything else is alias
     pub fn new(input: &str) -> Result<Self> {
-        if let Ok(alias) = Alias(input.to_string()).validated() {
-            Ok(Self::Alias(alias.to_owned()))
+        if let Ok(thesis_id) =
+            serde_json::from_value::<ObjectId>(serde_json::Value::String(input.to_string()))
+        {
+            Ok(Self::ObjectId(thesis_id))
+        } else if let Some(prefix) = input.strip_prefix('~') {
+            if (MINIMAL_ID_PREFIX_LENGTH..22).contains(&prefix.len())
+                && prefix
+                    .chars()
+                    .all(|character| character.is_ascii_alphanumeric() || "-_".contains(character))
+            {
+                Ok(Self::IdPrefix(prefix.to_string()))
+            } else {
+                Err(anyhow!(
+                    "Can not parse thesis id prefix {input:?}, it must be from \
+                     {MINIMAL_ID_PREFIX_LENGTH} to 21 thesis id characters after '~'"
+                ))
+            }
         } else {
-            Ok(Self::ObjectId(serde_json::from_value(
-                serde_json::Value::String(input.to_string()),
-            )?))
+            Ok(Self::Alias(
+                Alias(input.to_string()).validated()?.to_owned(),
+            ))
         }
     }
 }
@@ -42,10 +67,10 @@ pub enum Command {
 }
 
 impl Command {
-    pub fn validated(&self) -> Result<&Self> {
+    pub fn validated(&self, charset: &TextCharset) -> Result<&Self> {
         match self {
             Command::AddThesis(thesis) => {
-                thesis.validated()?;
+                thesis.validated(charset)?;
             }
             Command::RemoveThesis(_) => {}
             Command::AddTags(_, tags) => {
@@ -66,179 +91,664 @@ impl Command {
     }
 }
 
-pub struct CommandsIterator<'a> {
-    supported_relations_kinds: &'a BTreeSet<RelationKind>,
-    paragraphs_iterator: Box<dyn FallibleIterator<Item = (usize, &'a str), Error = Error> + 'a>,
-    aliases_resolver: &'a mut AliasesResolver<'a>,
+pub struct CommandsIterator<'a, 'b> {
+    supported_relations_kinds: &'b BTreeSet<RelationKind>,
+    paragraphs_iterator: Box<dyn FallibleIterator<Item = Paragraph, Error = Error> + 'b>,
+    aliases_resolver: &'b mut AliasesResolver<'a>,
+    // commands parsed from paragraph which are not yielded yet, as one paragraph can be parsed
+    // into several commands
+    pending_commands: VecDeque<Command>,
+    // namespace is scoped to file it is started in, so namespace of including file is neither
+    // seen in included one nor changed by it
+    namespaces: BTreeMap<Option<PathBuf>, String>,
+    source: Option<PathBuf>,
+    apply_mode: ApplyMode,
 }
 
-impl<'a> CommandsIterator<'a> {
+impl<'a, 'b> CommandsIterator<'a, 'b> {
     pub fn new(
-        input: &'a str,
-        supported_relations_kinds: &'a BTreeSet<RelationKind>,
-        aliases_resolver: &'a mut AliasesResolver<'a>,
+        input: &'b str,
+        supported_relations_kinds: &'b BTreeSet<RelationKind>,
+        aliases_resolver: &'b mut AliasesResolver<'a>,
     ) -> Self {
-        static COMMANDS_SPLIT_REGEX: std::sync::OnceLock<Regex> = std::sync::OnceLock::new();
-        let commands_split_regex = COMMANDS_SPLIT_REGEX.get_or_init(|| {
-            Regex::new(r#"(\r?\n|\r){2,}"#)
-                .with_context(|| "Can not compile regular expression for commands splitting")
-                .unwrap()
-        });
         Self {
             supported_relations_kinds,
             aliases_resolver: aliases_resolver,
-            paragraphs_iterator: Box::new(fallible_iterator::convert(
-                commands_split_regex
-                    .split(input)
-                    .map(|paragraph| paragraph.trim())
-                    .filter(|paragraph| !paragraph.is_empty())
-                    .enumerate()
-                    .map(|index_and_paragraph| Ok(index_and_paragraph)),
-            )),
+            paragraphs_iterator: Box::new(ParagraphsIterator::new(input)),
+            pending_commands: VecDeque::new(),
+            namespaces: BTreeMap::new(),
+            source: None,
+            apply_mode: ApplyMode::default(),
         }
     }
-}
 
-impl<'a> FallibleIterator for CommandsIterator<'a> {
-    type Item = Command;
-    type Error = Error;
+    // parses paragraphs read from reader one by one, so input does not have to fit in memory
+    pub fn from_reader<R: BufRead + 'b>(
+        reader: R,
+        supported_relations_kinds: &'b BTreeSet<RelationKind>,
+        aliases_resolver: &'b mut AliasesResolver<'a>,
+    ) -> Self {
+        Self {
+            supported_relations_kinds,
+            aliases_resolver,
+            paragraphs_iterator: Box::new(ReaderParagraphsIterator::new(reader)),
+            pending_commands: VecDeque::new(),
+            namespaces: BTreeMap::new(),
+            source: None,
+            apply_mode: ApplyMode::default(),
+        }
+    }
 
-    fn next(&mut self) -> Result<Option<Self::Item>> {
-        if let Some((paragraph_index, paragraph)) = self.paragraphs_iterator.next()? {
-            let lines = paragraph.split('\n').collect::<Vec<_>>();
-            static COMMAND_FIRST_LINE_REGEX: std::sync::OnceLock<Regex> =
-                std::sync::OnceLock::new();
-            let command_first_line_regex = COMMAND_FIRST_LINE_REGEX.get_or_init(|| {
-                Regex::new(r#"^ *(\+|-|#|\^|@)(:? +([^ ]+))? *$"#)
-                    .with_context(|| "Can not compile regular expression for commands splitting")
-                    .unwrap()
-            });
-            if let Some(captures) = command_first_line_regex.captures(lines[0]) {
-                let operation_char = captures[1].chars().next().unwrap();
-                let alias_option = captures
-                    .get(3)
-                    .map(|alias_match| Alias(alias_match.as_str().to_string()));
-                if let Some(ref alias) = alias_option {
-                    alias.validated().with_context(|| {
-                        format!(
-                            "Can not parse first line {:?} in {}-nth paragraph {:?}",
-                            lines[0],
-                            paragraph_index + 1,
-                            paragraph
-                        )
-                    })?;
+    pub fn from_file(
+        path: &Path,
+        supported_relations_kinds: &'b BTreeSet<RelationKind>,
+        aliases_resolver: &'b mut AliasesResolver<'a>,
+    ) -> Result<Self> {
+        Ok(Self {
+            supported_relations_kinds,
+            aliases_resolver,
+            paragraphs_iterator: Box::new(FilesParagraphsIterator::new(path)?),
+            pending_commands: VecDeque::new(),
+            namespaces: BTreeMap::new(),
+            source: None,
+            apply_mode: ApplyMode::default(),
+        })
+    }
+
+    // commands are parsed for applying in given mode, so that in idempotent mode removing thesis
+    // by alias which is already removed is no-op too
+    pub fn in_mode(mut self, apply_mode: ApplyMode) -> Self {
+        self.apply_mode = apply_mode;
+        self
+    }
+
+    // two-pass mode: first pass collects aliases declared in all paragraphs, second one parses
+    // paragraphs in input order, except that paragraphs referencing aliases which are declared
+    // only later in input are deferred right after these declarations; alias is declared by '+'
+    // once and may be declared again by '@', and reference is bound to the nearest declaration
+    // before it or, if there is none and alias is not known yet, to the first one after it
+    pub fn collect_with_forward_references(mut self) -> Result<Vec<Command>> {
+        let mut paragraphs = Vec::new();
+        while let Some(paragraph) = self.next_paragraph()? {
+            paragraphs.push(self.with_namespace_expanded(&paragraph));
+        }
+
+        let mut declarations: BTreeMap<Alias, Vec<usize>> = BTreeMap::new();
+        let mut added: BTreeMap<Alias, usize> = BTreeMap::new();
+        for (paragraph_index, paragraph) in paragraphs.iter().enumerate() {
+            if let Some(captures) = Self::command_first_line_regex().captures(&paragraph.lines[0])
+                && let Some(alias_match) = captures.get(3)
+                && matches!(&captures[1], "+" | "@")
+            {
+                let alias = Alias(alias_match.as_str().to_string());
+                if &captures[1] == "+"
+                    && let Some(adding_paragraph_index) =
+                        added.insert(alias.clone(), paragraph_index)
+                {
+                    return Err(anyhow!(
+                        "Duplicate alias {:?} declared in {} and in {paragraph}",
+                        alias.0,
+                        paragraphs[adding_paragraph_index]
+                    ));
                 }
-                Ok(Some(
-                    match (operation_char, lines.len()) {
-                        ('+', 2) => {
-                            let thesis = Thesis {
-                                alias: alias_option.clone(),
-                                content: Content::Text(Text::new(lines[1], self.aliases_resolver)?),
-                                tags: vec![],
-                            };
-                            if let Some(ref alias) = alias_option {
-                                self.aliases_resolver.remember(alias.clone(), thesis.id()?);
-                            }
-                            Command::AddThesis(thesis)
-                        }
-                        ('+', 4) => {
-                            let thesis = Thesis {
-                                alias: alias_option.clone(),
-                                content: Content::Relation(Relation {
-                                    from: self
-                                        .aliases_resolver
-                                        .get_thesis_id_by_reference(&Reference::new(lines[1])?)
-                                        .with_context(|| {
-                                            format!(
-                                                "Can not parse relation for AddThesis command on \
-                                                 {}-th paragraph {:?}",
-                                                paragraph_index + 1,
-                                                paragraph
-                                            )
-                                        })?,
-                                    kind: RelationKind(lines[2].to_string()),
-                                    to: self
-                                        .aliases_resolver
-                                        .get_thesis_id_by_reference(&Reference::new(lines[3])?)?,
-                                }),
-                                tags: vec![],
-                            };
-                            if let Some(ref alias) = alias_option {
-                                self.aliases_resolver.remember(alias.clone(), thesis.id()?);
+                declarations.entry(alias).or_default().push(paragraph_index);
+            }
+        }
+        let mut dependencies: Vec<Vec<Alias>> = Vec::with_capacity(paragraphs.len());
+        for paragraph in paragraphs.iter() {
+            let lines = paragraph
+                .lines
+                .iter()
+                .map(|line| line.as_str())
+                .collect::<Vec<_>>();
+            let mut paragraph_dependencies = Vec::new();
+            if let Some(captures) = Self::command_first_line_regex().captures(lines[0]) {
+                let references = match &captures[1] {
+                    "+" if self
+                        .is_relation(&lines)
+                        .with_context(|| format!("Can not parse {paragraph}"))? =>
+                    {
+                        std::iter::once(lines[1])
+                            .chain(
+                                lines[3..]
+                                    .iter()
+                                    .copied()
+                                    .filter(|line| self.relation_kind(line).is_none()),
+                            )
+                            .collect()
+                    }
+                    "+" => {
+                        paragraph_dependencies
+                            .extend(Text::referenced_aliases(&lines[1..].join("\n")));
+                        Vec::new()
+                    }
+                    _ => lines.get(1).into_iter().copied().collect(),
+                };
+                for reference in references {
+                    if let Ok(Reference::Alias(alias)) = Reference::new(reference) {
+                        paragraph_dependencies.push(alias);
+                    }
+                }
+            }
+            dependencies.push(paragraph_dependencies);
+        }
+
+        // paragraph goes after paragraphs declaring aliases it references
+        let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); paragraphs.len()];
+        let mut dependencies_counts = vec![0usize; paragraphs.len()];
+        let mut undefined = Vec::new();
+        for (paragraph_index, paragraph_dependencies) in dependencies.iter().enumerate() {
+            for alias in paragraph_dependencies {
+                let declaring_paragraphs_indices = declarations
+                    .get(alias)
+                    .map(|indices| indices.as_slice())
+                    .unwrap_or_default();
+                let preceding_count = declaring_paragraphs_indices
+                    .partition_point(|declaring_index| *declaring_index < paragraph_index);
+                let declaring_paragraph_index = if preceding_count > 0 {
+                    declaring_paragraphs_indices[preceding_count - 1]
+                } else if self
+                    .aliases_resolver
+                    .get_thesis_id_by_reference(&Reference::Alias(alias.clone()))
+                    .is_ok()
+                {
+                    continue;
+                } else if let Some(declaring_paragraph_index) = declaring_paragraphs_indices
+                    .iter()
+                    .find(|declaring_index| **declaring_index > paragraph_index)
+                {
+                    *declaring_paragraph_index
+                } else {
+                    if !declaring_paragraphs_indices.contains(&paragraph_index) {
+                        undefined.push(format!("{:?} in {}", alias.0, paragraphs[paragraph_index]));
+                    }
+                    continue;
+                };
+                dependents[declaring_paragraph_index].push(paragraph_index);
+                dependencies_counts[paragraph_index] += 1;
+            }
+        }
+        if !undefined.is_empty() {
+            return Err(anyhow!(
+                "Can not find declarations of aliases {}",
+                undefined.join(", ")
+            ));
+        }
+
+        // of paragraphs which dependencies are already parsed, the first one in input is parsed
+        let mut ready = dependencies_counts
+            .iter()
+            .enumerate()
+            .filter(|(_, dependencies_count)| **dependencies_count == 0)
+            .map(|(paragraph_index, _)| std::cmp::Reverse(paragraph_index))
+            .collect::<BinaryHeap<_>>();
+        let mut result = Vec::with_capacity(paragraphs.len());
+        let mut parsed_count = 0;
+        while let Some(std::cmp::Reverse(paragraph_index)) = ready.pop() {
+            result.extend(self.commands(&paragraphs[paragraph_index])?);
+            parsed_count += 1;
+            for dependent_index in dependents[paragraph_index].iter() {
+                dependencies_counts[*dependent_index] -= 1;
+                if dependencies_counts[*dependent_index] == 0 {
+                    ready.push(std::cmp::Reverse(*dependent_index));
+                }
+            }
+        }
+        if parsed_count < paragraphs.len() {
+            return Err(anyhow!(
+                "Can not order {}: they depend on each other through aliases",
+                paragraphs
+                    .iter()
+                    .zip(dependencies_counts.iter())
+                    .filter(|(_, dependencies_count)| **dependencies_count > 0)
+                    .map(|(paragraph, _)| paragraph.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" and ")
+            ));
+        }
+        Ok(result)
+    }
+
+    // unlike next, does not stop on invalid command, so that all paragraphs can be checked
+    // paragraph is returned as it is in input, while commands are parsed from it with aliases
+    // expanded in current namespace
+    pub fn next_paragraph_commands(&mut self) -> Result<Option<(Paragraph, Result<Vec<Command>>)>> {
+        if let Some(paragraph) = self.next_paragraph()? {
+            let commands = self.commands(&self.with_namespace_expanded(&paragraph));
+            Ok(Some((paragraph, commands)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // next paragraph which is not namespace directive, namespace directives are applied
+    fn next_paragraph(&mut self) -> Result<Option<Paragraph>> {
+        while let Some(paragraph) = self.paragraphs_iterator.next()? {
+            self.source = paragraph.source.clone();
+            if paragraph.lines.len() == 1
+                && let Some(namespace) = Self::namespace_directive(&paragraph.lines[0])
+            {
+                match namespace {
+                    Some(namespace) => {
+                        let namespace = Self::expanded_alias(self.namespace(), namespace);
+                        self.namespaces.insert(self.source.clone(), namespace);
+                    }
+                    None => {
+                        self.namespaces.remove(&self.source);
+                    }
+                }
+                continue;
+            }
+            return Ok(Some(paragraph));
+        }
+        Ok(None)
+    }
+
+    // single line paragraph "namespace PREFIX" starts namespace in which aliases starting with
+    // '.' are prefixed with PREFIX, e.g. ".d" is "(R-r).d" in namespace "(R-r)", and "namespace"
+    // without prefix ends it
+    pub(crate) fn namespace_directive(line: &str) -> Option<Option<&str>> {
+        static NAMESPACE_REGEX: std::sync::OnceLock<Regex> = std::sync::OnceLock::new();
+        let namespace_regex = NAMESPACE_REGEX.get_or_init(|| {
+            Regex::new(r#"^ *namespace(?: +([^ ]+))? *$"#)
+                .with_context(|| "Can not compile regular expression for namespace directive")
+                .unwrap()
+        });
+        namespace_regex.captures(line).map(|captures| {
+            captures
+                .get(1)
+                .map(|namespace_match| namespace_match.as_str())
+        })
+    }
+
+    pub fn namespace(&self) -> Option<&str> {
+        self.namespaces.get(&self.source).map(String::as_str)
+    }
+
+    pub fn expanded_alias(namespace: Option<&str>, alias: &str) -> String {
+        match namespace {
+            Some(namespace) if alias.starts_with('.') => format!("{namespace}{alias}"),
+            _ => alias.to_string(),
+        }
+    }
+
+    fn with_namespace_expanded(&self, paragraph: &Paragraph) -> Paragraph {
+        let mut result = paragraph.clone();
+        let namespace = self.namespace();
+        if namespace.is_none() {
+            return result;
+        }
+        let lines = paragraph
+            .lines
+            .iter()
+            .map(|line| line.as_str())
+            .collect::<Vec<_>>();
+        let Some(captures) = Self::command_first_line_regex().captures(lines[0]) else {
+            return result;
+        };
+        if let Some(alias_match) = captures.get(3) {
+            result.lines[0].replace_range(
+                alias_match.range(),
+                &Self::expanded_alias(namespace, alias_match.as_str()),
+            );
+        }
+        match &captures[1] {
+            // paragraph looking like relation of unsupported kind is expanded as relation, and
+            // it's error is reported when it is parsed
+            "+" if self.is_relation(&lines).unwrap_or(true) => {
+                for (line_index, line) in lines.iter().enumerate().skip(1) {
+                    if line_index == 1 || self.relation_kind(line).is_none() {
+                        result.lines[line_index] = Self::expanded_alias(namespace, line);
+                    }
+                }
+            }
+            "+" => {
+                for (line_index, line) in lines.iter().enumerate().skip(1) {
+                    result.lines[line_index] = Text::reference_in_text_regex()
+                        .replace_all(line, |captures: &regex::Captures| {
+                            let whole_match = captures.get(0).unwrap();
+                            match captures.get(4) {
+                                Some(alias_match) => format!(
+                                    "{}{}{}",
+                                    &line[whole_match.start()..alias_match.start()],
+                                    Self::expanded_alias(namespace, alias_match.as_str()),
+                                    &line[alias_match.end()..whole_match.end()]
+                                ),
+                                None => whole_match.as_str().to_string(),
                             }
-                            Command::AddThesis(thesis)
-                        }
-                        ('-', 2) => Command::RemoveThesis(
-                            self.aliases_resolver
-                                .get_thesis_id_by_reference(&Reference::new(lines[1])?)?,
-                        ),
-                        ('#', 3..) => Command::AddTags(
-                            self.aliases_resolver
-                                .get_thesis_id_by_reference(&Reference::new(lines[1])?)?,
-                            lines[2..]
-                                .iter()
-                                .map(|tag_string| Tag(tag_string.to_string()))
-                                .collect(),
-                        ),
-                        ('^', 3..) => Command::RemoveTags(
-                            self.aliases_resolver
-                                .get_thesis_id_by_reference(&Reference::new(lines[1])?)?,
-                            lines[2..]
-                                .iter()
-                                .map(|tag_string| Tag(tag_string.to_string()))
-                                .collect(),
-                        ),
-                        ('@', 2) => {
-                            let thesis_id = self
+                        })
+                        .to_string();
+                }
+            }
+            "-" | "#" | "^" | "@" if lines.len() > 1 => {
+                result.lines[1] = Self::expanded_alias(namespace, lines[1]);
+            }
+            _ => {}
+        }
+        result
+    }
+
+    pub(crate) fn command_first_line_regex() -> &'static Regex {
+        static COMMAND_FIRST_LINE_REGEX: std::sync::OnceLock<Regex> = std::sync::OnceLock::new();
+        COMMAND_FIRST_LINE_REGEX.get_or_init(|| {
+            Regex::new(r#"^ *(\+|-|#|\^|@)(:? +([^ #][^ ]*))?((?: +#[^ ]*)*) *$"#)
+                .with_context(|| "Can not compile regular expression for commands splitting")
+                .unwrap()
+        })
+    }
+
+    // supported relation kind or it's shorthand; kinds are supported only if they are in given
+    // set, sweater config just names them with shorthands
+    fn relation_kind(&self, line: &str) -> Option<RelationKind> {
+        let relation_kind = RelationKind(line.to_string());
+        if self.supported_relations_kinds.contains(&relation_kind) {
+            return Some(relation_kind);
+        }
+        self.aliases_resolver
+            .read_able_transaction
+            .sweater_config()
+            .relations_kinds_shorthands
+            .iter()
+            .find(|(relation_kind, shorthands)| {
+                self.supported_relations_kinds.contains(relation_kind)
+                    && shorthands.iter().any(|shorthand| shorthand == line)
+            })
+            .map(|(relation_kind, _)| relation_kind.clone())
+    }
+
+    // lines of paragraph adding thesis are relation if there are four or more of them with
+    // relation kind in the third one, whatever other lines are, so that unknown references in
+    // relation are reported rather than turn it into text; four lines shaped like reference,
+    // relation kind and reference, but with not supported relation kind, are error, as they look
+    // like relation of unsupported kind rather than text; only lines shapes and supported
+    // relations kinds are considered, so that input is parsed the same way with any sweater
+    pub(crate) fn is_relation_lines(
+        lines: &[&str],
+        is_relation_kind: impl Fn(&str) -> bool,
+    ) -> Result<bool> {
+        if lines.len() < 4 {
+            return Ok(false);
+        }
+        if is_relation_kind(lines[2]) {
+            return Ok(true);
+        }
+        if lines.len() == 4
+            && Self::looks_like_reference(lines[1])
+            && RelationKind(lines[2].to_string()).validated().is_ok()
+            && Self::looks_like_reference(lines[3])
+        {
+            return Err(anyhow!(
+                "it looks like relation, as it's second and fourth lines are single words and \
+                 it's third line is words sequence, but {:?} is not supported relation kind",
+                lines[2]
+            ));
+        }
+        Ok(false)
+    }
+
+    // single word which can be thesis id, id prefix or alias
+    fn looks_like_reference(line: &str) -> bool {
+        !line.contains(char::is_whitespace) && Reference::new(line).is_ok()
+    }
+
+    fn is_relation(&self, lines: &[&str]) -> Result<bool> {
+        Self::is_relation_lines(lines, |line| self.relation_kind(line).is_some())
+    }
+
+    // relations paragraph is thesis from which relations are, followed by one or more relation
+    // kinds, each followed by one or more theses to which relations of this kind are, e.g.
+    // R, may be, R-r, R-a, means, R-m lines add three relations from R
+    fn relations(
+        &mut self,
+        paragraph: &Paragraph,
+        alias_option: Option<Alias>,
+        tags: Vec<Tag>,
+    ) -> Result<Vec<Command>> {
+        let context = |line_index: usize| {
+            format!(
+                "Can not parse relation on line {} of {paragraph}, it is parsed as relation \
+                 because of relation kind in it's third line",
+                paragraph.lines_numbers[line_index]
+            )
+        };
+        let mut errors = Vec::new();
+        let from = Reference::new(&paragraph.lines[1])
+            .and_then(|reference| self.aliases_resolver.get_thesis_id_by_reference(&reference))
+            .with_context(|| context(1))
+            .map_err(|error| errors.push(error))
+            .ok();
+        let mut theses = Vec::new();
+        let mut kind: Option<(usize, RelationKind)> = None;
+        let mut kind_targets_count = 0;
+        for (line_index, line) in paragraph.lines.iter().enumerate().skip(2) {
+            if let Some(relation_kind) = self.relation_kind(line) {
+                if let Some((kind_line_index, _)) = kind
+                    && kind_targets_count == 0
+                {
+                    errors.push(anyhow!(
+                        "{}: there is no thesis to which relation is after relation kind",
+                        context(kind_line_index)
+                    ));
+                }
+                kind = Some((line_index, relation_kind));
+                kind_targets_count = 0;
+                continue;
+            }
+            kind_targets_count += 1;
+            match Reference::new(line)
+                .and_then(|reference| self.aliases_resolver.get_thesis_id_by_reference(&reference))
+                .with_context(|| context(line_index))
+            {
+                Ok(to) => {
+                    if let Some(ref from) = from {
+                        theses.push(Thesis {
+                            alias: None,
+                            content: Content::Relation(Relation {
+                                from: from.clone(),
+                                kind: kind.as_ref().unwrap().1.clone(),
+                                to,
+                            }),
+                            tags: tags.clone(),
+                        });
+                    }
+                }
+                Err(error) => errors.push(error),
+            }
+        }
+        if kind_targets_count == 0 {
+            errors.push(anyhow!(
+                "{}: there is no thesis to which relation is after relation kind",
+                context(kind.unwrap().0)
+            ));
+        }
+        if !errors.is_empty() {
+            return Err(anyhow!(
+                "{}",
+                errors
+                    .iter()
+                    .map(|error| format!("{error:#}"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ));
+        }
+        if let Some(alias) = alias_option {
+            if theses.len() > 1 {
+                return Err(anyhow!(
+                    "Can not set alias {:?} in {paragraph}: it adds {} relations, while alias \
+                     can be set only for single one",
+                    alias.0,
+                    theses.len()
+                ));
+            }
+            let thesis_id = self
+                .aliases_resolver
+                .read_able_transaction
+                .thesis_id(&theses[0])?;
+            self.aliases_resolver.remember(alias.clone(), thesis_id);
+            theses[0].alias = Some(alias);
+        }
+        Ok(theses.into_iter().map(Command::AddThesis).collect())
+    }
+
+    fn commands(&mut self, paragraph: &Paragraph) -> Result<Vec<Command>> {
+        let lines = paragraph
+            .lines
+            .iter()
+            .map(|line| line.as_str())
+            .collect::<Vec<_>>();
+        if let Some(captures) = Self::command_first_line_regex().captures(lines[0]) {
+            let operation_char = captures[1].chars().next().unwrap();
+            let alias_option = captures
+                .get(3)
+                .map(|alias_match| Alias(alias_match.as_str().to_string()));
+            if let Some(ref alias) = alias_option {
+                alias.validated().with_context(|| {
+                    format!("Can not parse first line {:?} in {paragraph}", lines[0])
+                })?;
+            }
+            let inline_tags = captures[4]
+                .split_whitespace()
+                .map(|tag_string| Tag(tag_string[1..].to_string()))
+                .collect::<Vec<_>>();
+            if !inline_tags.is_empty() && operation_char != '+' {
+                return Err(anyhow!(
+                    "Can not parse first line {:?} in {paragraph}: tags on first line are \
+                     supported only for adding thesis with '+' character",
+                    lines[0]
+                ));
+            }
+            let is_relation = operation_char == '+'
+                && self
+                    .is_relation(&lines)
+                    .with_context(|| format!("Can not parse {paragraph}"))?;
+            let commands = match (operation_char, lines.len()) {
+                ('+', 4..) if is_relation => {
+                    self.relations(paragraph, alias_option, inline_tags)?
+                }
+                ('+', 2..) => {
+                    let thesis = Thesis {
+                        alias: alias_option.clone(),
+                        content: Content::Text(Text::new(
+                            &lines[1..].join("\n"),
+                            self.aliases_resolver,
+                        )?),
+                        tags: inline_tags,
+                    };
+                    if let Some(ref alias) = alias_option {
+                        let thesis_id = self
+                            .aliases_resolver
+                            .read_able_transaction
+                            .thesis_id(&thesis)?;
+                        self.aliases_resolver.remember(alias.clone(), thesis_id);
+                    }
+                    vec![Command::AddThesis(thesis)]
+                }
+                // in idempotent mode removed thesis is not required to exist, as removing missing
+                // thesis is no-op then, so that removal commands can be safely applied again
+                ('-', 2) => match Reference::new(lines[1])? {
+                    Reference::ObjectId(thesis_id) if self.apply_mode == ApplyMode::Idempotent => {
+                        vec![Command::RemoveThesis(thesis_id)]
+                    }
+                    Reference::Alias(ref alias)
+                        if self.apply_mode == ApplyMode::Idempotent
+                            && !self.aliases_resolver.known_aliases.contains_key(alias)
+                            && self
                                 .aliases_resolver
-                                .get_thesis_id_by_reference(&Reference::new(lines[1])?)?;
-                            let alias = alias_option.ok_or_else(|| {
-                                anyhow!(
-                                    "Can not parse {}-th paragraph {paragraph:?}: looks like it \
-                                     is command for setting alias, yet there is no new alias \
-                                     provided in first line after '@' character",
-                                    paragraph_index + 1
-                                )
-                            })?;
-                            self.aliases_resolver
-                                .remember(alias.clone(), thesis_id.clone());
-                            Command::SetAlias(thesis_id, alias)
-                        }
-                        _ => {
-                            return Err(anyhow!(
-                                "Unsupported operation character and lines count combination \
-                                 ({:?}, {}) in first line {:?} of {}-th paragraph {:?}, supported \
-                                 combinations are ('+', 2) for adding text thesis, ('+', 4) for \
-                                 adding relation thesis, ('-', 2) for removing thesis, ('#', 3) \
-                                 for adding tag, ('^', 3) for removing tag",
-                                operation_char,
-                                lines.len(),
-                                lines[0],
-                                paragraph_index + 1,
-                                paragraph
-                            ));
-                        }
+                                .read_able_transaction
+                                .get_thesis_id_by_alias(alias)?
+                                .is_none() =>
+                    {
+                        Vec::new()
                     }
-                    .validated()
-                    .with_context(|| {
-                        format!(
-                            "Invalid command parsed from {}-th paragraph {:?}",
-                            paragraph_index + 1,
-                            paragraph
+                    reference => vec![Command::RemoveThesis(
+                        self.aliases_resolver
+                            .get_thesis_id_by_reference(&reference)?,
+                    )],
+                },
+                ('#', 3..) => vec![Command::AddTags(
+                    self.aliases_resolver
+                        .get_thesis_id_by_reference(&Reference::new(lines[1])?)?,
+                    lines[2..]
+                        .iter()
+                        .map(|tag_string| Tag(tag_string.to_string()))
+                        .collect(),
+                )],
+                ('^', 3..) => vec![Command::RemoveTags(
+                    self.aliases_resolver
+                        .get_thesis_id_by_reference(&Reference::new(lines[1])?)?,
+                    lines[2..]
+                        .iter()
+                        .map(|tag_string| Tag(tag_string.to_string()))
+                        .collect(),
+                )],
+                ('@', 2) => {
+                    let thesis_id = self
+                        .aliases_resolver
+                        .get_thesis_id_by_reference(&Reference::new(lines[1])?)?;
+                    let alias = alias_option.ok_or_else(|| {
+                        anyhow!(
+                            "Can not parse {paragraph}: looks like it is command for \
+                             setting alias, yet there is no new alias provided in first \
+                             line after '@' character"
                         )
-                    })?
-                    .to_owned(),
-                ))
-            } else {
-                Err(anyhow!(
-                    "Can not parse first line {:?} in {}-th paragraph {:?}",
-                    lines[0],
-                    paragraph_index + 1,
-                    paragraph
-                ))
+                    })?;
+                    self.aliases_resolver
+                        .remember(alias.clone(), thesis_id.clone());
+                    vec![Command::SetAlias(thesis_id, alias)]
+                }
+                _ => {
+                    return Err(anyhow!(
+                        "Unsupported operation character and lines count combination \
+                         ({:?}, {}) in first line {:?} of {paragraph}, supported \
+                         combinations are ('+', 4 or more) with supported relation kind \
+                         in third line for adding relation theses, ('+', 2 or more) for \
+                         adding text thesis, ('-', 2) for removing thesis, ('#', 3 or \
+                         more) for adding tags, ('^', 3 or more) for removing tags, ('@', \
+                         2) for setting alias",
+                        operation_char,
+                        lines.len(),
+                        lines[0]
+                    ));
+                }
+            };
+            for command in commands.iter() {
+                command
+                    .validated(
+                        &self
+                            .aliases_resolver
+                            .read_able_transaction
+                            .sweater_config()
+                            .text_charset,
+                    )
+                    .with_context(|| format!("Invalid command parsed from {paragraph}"))?;
             }
+            Ok(commands)
+        } else if lines[0].trim_start().starts_with("include ") {
+            Err(anyhow!(
+                "Can not process include directive {:?} in {paragraph}: includes are only \
+                 supported for commands parsed from files",
+                lines[0]
+            ))
         } else {
-            Ok(None)
+            Err(anyhow!(
+                "Can not parse first line {:?} in {paragraph}",
+                lines[0]
+            ))
+        }
+    }
+}
+
+impl<'a, 'b> FallibleIterator for CommandsIterator<'a, 'b> {
+    type Item = Command;
+    type Error = Error;
+
+    fn next(&mut self) -> Result<Option<Self::Item>> {
+        while self.pending_commands.is_empty() {
+            match self.next_paragraph_commands()? {
+                Some((_, commands)) => self.pending_commands.extend(commands?),
+                None => return Ok(None),
+            }
         }
+        Ok(self.pending_commands.pop_front())
     }
 }
@@ -2,8 +2,9 @@ use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use trove::ObjectId;
 
+use crate::id_scheme::{IdScheme, IdSchemeVersion};
 use crate::relation::Relation;
-use crate::text::Text;
+use crate::text::{Text, TextCharset};
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum Content {
@@ -12,29 +13,57 @@ pub enum Content {
 }
 
 impl Content {
-    pub fn id(&self) -> Result<ObjectId> {
-        let source = match self {
-            Content::Text(text) => text.composed().bytes().collect(),
-            Content::Relation(relation) => {
-                bincode::encode_to_vec(relation, bincode::config::standard()).with_context(
-                    || {
-                        format!(
-                            "Can not binary encode Content {self:?} in order to compute it's \
-                             ObjectId as it's binary representation hash"
-                        )
-                    },
-                )?
+    // text is encoded with it's composed form, where references labels are included as
+    // [reference|label], so theses differing only in labels have different ids, while ids of
+    // texts without labels are the same as before labels were introduced
+    //
+    // canonical encoding of version 2 is b"woollib/2" followed by one content type byte: for text
+    // it is b't' followed by composed text UTF-8 bytes, for relation it is b'r' followed by 16
+    // bytes of from thesis id, 16 bytes of to thesis id and relation kind UTF-8 bytes
+    pub fn encoded(&self, version: IdSchemeVersion) -> Result<Vec<u8>> {
+        Ok(match version {
+            IdSchemeVersion::V1 => match self {
+                Content::Text(text) => text.composed().bytes().collect(),
+                Content::Relation(relation) => {
+                    bincode::encode_to_vec(relation, bincode::config::standard()).with_context(
+                        || {
+                            format!(
+                                "Can not binary encode Content {self:?} in order to compute it's \
+                                 ObjectId as it's binary representation hash"
+                            )
+                        },
+                    )?
+                }
+            },
+            IdSchemeVersion::V2 => {
+                let mut result = b"woollib/2".to_vec();
+                match self {
+                    Content::Text(text) => {
+                        result.push(b't');
+                        result.extend(text.composed().bytes());
+                    }
+                    Content::Relation(relation) => {
+                        result.push(b'r');
+                        result.extend(relation.from.value);
+                        result.extend(relation.to.value);
+                        result.extend(relation.kind.0.bytes());
+                    }
+                }
+                result
             }
-        };
+        })
+    }
+
+    pub fn id_with(&self, id_scheme: &IdScheme) -> Result<ObjectId> {
         Ok(ObjectId {
-            value: xxhash_rust::xxh3::xxh3_128(&source).to_be_bytes(),
+            value: id_scheme.hash(&self.encoded(id_scheme.version)?),
         })
     }
 
-    pub fn validated(&self) -> Result<&Self> {
+    pub fn validated(&self, charset: &TextCharset) -> Result<&Self> {
         match self {
             Content::Text(text) => {
-                text.validated()?;
+                text.validated(charset)?;
             }
             Content::Relation(relation) => {
                 relation.validated()?;
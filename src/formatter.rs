@@ -0,0 +1,169 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use fallible_iterator::FallibleIterator;
+
+use crate::commands::{CommandsIterator, Reference};
+use crate::paragraphs::{is_comment, FilesParagraphsIterator, Paragraph, ParagraphsIterator};
+use crate::read_transaction::ReadTransactionMethods;
+use crate::text::Text;
+
+// formats commands input to canonical form: paragraphs are delimited with exactly one empty line,
+// comments are kept in place, first lines are written as operation character followed by alias
+// and tags delimited with single spaces, other lines are trimmed and text lines have whitespaces
+// runs collapsed; if read transaction is given, theses ids having aliases in it are replaced
+// with these aliases, otherwise relation kinds are not known, so whitespaces runs are collapsed
+// only in paragraphs adding thesis of two or three lines, which are always texts
+pub fn format<'a>(
+    input: &str,
+    aliases_source: Option<&'a dyn ReadTransactionMethods<'a>>,
+) -> Result<String> {
+    let mut formatted_lines = BTreeMap::new();
+    let mut paragraphs = ParagraphsIterator::new(input);
+    while let Some(paragraph) = paragraphs.next()? {
+        formatted_lines.extend(
+            paragraph
+                .lines_numbers
+                .iter()
+                .copied()
+                .zip(format_paragraph(&paragraph, aliases_source)?),
+        );
+    }
+
+    // paragraphs iterator skips comments, so they are taken from input and kept in place, and
+    // all other lines it skips are empty ones delimiting paragraphs
+    let mut result = String::new();
+    let mut is_paragraph_ended = false;
+    for (line_index, line) in input.lines().enumerate() {
+        let line = match formatted_lines.remove(&(line_index + 1)) {
+            Some(formatted_line) => formatted_line,
+            None if is_comment(line) => line.trim().to_string(),
+            None => {
+                is_paragraph_ended = !result.is_empty();
+                continue;
+            }
+        };
+        if is_paragraph_ended {
+            result.push('\n');
+            is_paragraph_ended = false;
+        }
+        result.push_str(&line);
+        result.push('\n');
+    }
+    Ok(result)
+}
+
+// returns formatted paragraph lines, one for each paragraph line
+fn format_paragraph<'a>(
+    paragraph: &Paragraph,
+    aliases_source: Option<&'a dyn ReadTransactionMethods<'a>>,
+) -> Result<Vec<String>> {
+    let mut result = paragraph
+        .lines
+        .iter()
+        .map(|line| line.trim().to_string())
+        .collect::<Vec<_>>();
+    let first_line = paragraph.lines[0].as_str();
+
+    if let Some(path) = FilesParagraphsIterator::include_directive_path(paragraph) {
+        result[0] = format!("include {}", path.display());
+        return Ok(result);
+    }
+    if result.len() == 1
+        && let Some(namespace) = CommandsIterator::namespace_directive(first_line)
+    {
+        result[0] = match namespace {
+            Some(namespace) => format!("namespace {namespace}"),
+            None => "namespace".to_string(),
+        };
+        return Ok(result);
+    }
+    let captures = CommandsIterator::command_first_line_regex()
+        .captures(first_line)
+        .ok_or_else(|| {
+            anyhow!("Can not format {paragraph}: can not parse first line {first_line:?}")
+        })?;
+    let operation_char = captures[1].chars().next().unwrap();
+    result[0] = std::iter::once(&captures[1])
+        .chain(captures.get(3).map(|alias_match| alias_match.as_str()))
+        .chain(captures[4].split_whitespace())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let is_relation_kind = |line: &str| {
+        aliases_source.is_some_and(|read_able_transaction| {
+            read_able_transaction
+                .sweater_config()
+                .relation_kind(line)
+                .is_some()
+        })
+    };
+    // without relation kinds paragraph of four or more lines can not be told from text, so it's
+    // lines are only trimmed, and paragraph looking like relation of unsupported kind is kept as
+    // relation
+    let is_relation = operation_char == '+'
+        && match aliases_source {
+            Some(_) => CommandsIterator::is_relation_lines(
+                &result.iter().map(String::as_str).collect::<Vec<_>>(),
+                is_relation_kind,
+            )
+            .unwrap_or(true),
+            None => result.len() >= 4,
+        };
+    for (position, line) in result.iter_mut().enumerate().skip(1) {
+        if operation_char == '+' && !is_relation {
+            *line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        }
+        if let Some(read_able_transaction) = aliases_source {
+            if operation_char == '+' && !is_relation {
+                *line = with_aliases_in_text(line, read_able_transaction)?;
+            } else if position == 1 || (is_relation && !is_relation_kind(line)) {
+                *line = with_alias(line, read_able_transaction)?;
+            }
+        }
+    }
+    Ok(result)
+}
+
+fn with_alias<'a>(
+    reference: &str,
+    read_able_transaction: &dyn ReadTransactionMethods<'a>,
+) -> Result<String> {
+    if let Ok(Reference::ObjectId(thesis_id)) = Reference::new(reference)
+        && let Some(alias) = read_able_transaction.get_alias_by_thesis_id(&thesis_id)?
+    {
+        Ok(alias.0)
+    } else {
+        Ok(reference.to_string())
+    }
+}
+
+fn with_aliases_in_text<'a>(
+    line: &str,
+    read_able_transaction: &dyn ReadTransactionMethods<'a>,
+) -> Result<String> {
+    let mut result = String::with_capacity(line.len());
+    let mut last_match_end = 0;
+    for captures in Text::reference_in_text_regex().captures_iter(line) {
+        let whole_match = captures.get(0).unwrap();
+        result.push_str(&line[last_match_end..whole_match.start()]);
+        last_match_end = whole_match.end();
+        match captures.get(3) {
+            Some(thesis_id_match) => {
+                result.push('[');
+                result.push_str(&with_alias(
+                    thesis_id_match.as_str(),
+                    read_able_transaction,
+                )?);
+                if let Some(label_match) = captures.get(5) {
+                    result.push('|');
+                    result.push_str(label_match.as_str());
+                }
+                result.push(']');
+            }
+            None => result.push_str(whole_match.as_str()),
+        }
+    }
+    result.push_str(&line[last_match_end..]);
+    Ok(result)
+}
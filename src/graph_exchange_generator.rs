@@ -0,0 +1,315 @@
+use anyhow::{Error, Result};
+use fallible_iterator::FallibleIterator;
+use serde::{Deserialize, Serialize};
+
+use crate::content::Content;
+use crate::read_transaction::ReadTransactionMethods;
+use crate::thesis::Thesis;
+
+#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum GraphExchangeFormat {
+    GraphML,
+    GEXF,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GraphExchangeGeneratorConfig {
+    pub format: GraphExchangeFormat,
+}
+
+pub enum Stage {
+    BeforeNodes,
+    Nodes,
+    Edges,
+    Finished,
+}
+
+struct Node {
+    id: String,
+    content: &'static str,
+    alias: Option<String>,
+    text: String,
+    tags: Vec<String>,
+}
+
+struct Edge {
+    id: String,
+    source: String,
+    target: String,
+    edge_type: &'static str,
+    kind: Option<String>,
+}
+
+pub struct GraphExchangeGenerator<'a> {
+    pub config: &'a GraphExchangeGeneratorConfig,
+    pub read_able_transaction: &'a dyn ReadTransactionMethods<'a>,
+    pub theses_iterator: Box<dyn FallibleIterator<Item = Thesis, Error = Error> + 'a>,
+    pub stage: Stage,
+}
+
+impl<'a> GraphExchangeGenerator<'a> {
+    pub fn new(
+        config: &'a GraphExchangeGeneratorConfig,
+        read_able_transaction: &'a dyn ReadTransactionMethods<'a>,
+    ) -> Result<Self> {
+        Ok(Self {
+            config,
+            read_able_transaction,
+            theses_iterator: Box::new(read_able_transaction.iter_theses()?),
+            stage: Stage::BeforeNodes,
+        })
+    }
+
+    // relation theses are edges, but ones which are themselves related or referenced also need
+    // node to be representable as endpoint
+    fn node(&self, thesis: &Thesis) -> Result<Option<Node>> {
+        let thesis_id = self.read_able_transaction.thesis_id(thesis)?;
+        let (content, text) = match thesis.content {
+            Content::Text(ref text) => (
+                "text",
+                text.composed_with_aliases(self.read_able_transaction)?,
+            ),
+            Content::Relation(ref relation) => {
+                if self
+                    .read_able_transaction
+                    .where_referenced(&thesis_id)?
+                    .is_empty()
+                {
+                    return Ok(None);
+                }
+                (
+                    "relation",
+                    self.read_able_transaction
+                        .sweater_config()
+                        .exported_relation_kind(&relation.kind)
+                        .to_string(),
+                )
+            }
+        };
+        Ok(Some(Node {
+            id: thesis_id.to_string(),
+            content,
+            alias: thesis.alias.as_ref().map(|alias| alias.0.clone()),
+            text,
+            tags: thesis.tags.iter().map(|tag| tag.0.clone()).collect(),
+        }))
+    }
+
+    fn edges(&self, thesis: &Thesis) -> Result<Vec<Edge>> {
+        let thesis_id_string = self.read_able_transaction.thesis_id(thesis)?.to_string();
+        Ok(match thesis.content {
+            Content::Text(ref text) => text
+                .references
+                .iter()
+                .enumerate()
+                .map(|(reference_index, referenced_thesis_id)| Edge {
+                    id: format!("reference-{thesis_id_string}-{reference_index}"),
+                    source: thesis_id_string.clone(),
+                    target: referenced_thesis_id.to_string(),
+                    edge_type: "reference",
+                    kind: None,
+                })
+                .collect(),
+            Content::Relation(ref relation) => vec![Edge {
+                id: format!("relation-{thesis_id_string}"),
+                source: relation.from.to_string(),
+                target: relation.to.to_string(),
+                edge_type: "relation",
+                kind: Some(
+                    self.read_able_transaction
+                        .sweater_config()
+                        .exported_relation_kind(&relation.kind)
+                        .to_string(),
+                ),
+            }],
+        })
+    }
+
+    fn header(&self) -> String {
+        match self.config.format {
+            GraphExchangeFormat::GraphML => concat!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+                "\n",
+                r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#,
+                "\n\t",
+                r#"<key id="content" for="node" attr.name="content" attr.type="string"/>"#,
+                "\n\t",
+                r#"<key id="alias" for="node" attr.name="alias" attr.type="string"/>"#,
+                "\n\t",
+                r#"<key id="text" for="node" attr.name="text" attr.type="string"/>"#,
+                "\n\t",
+                r#"<key id="tags" for="node" attr.name="tags" attr.type="string"/>"#,
+                "\n\t",
+                r#"<key id="type" for="edge" attr.name="type" attr.type="string"/>"#,
+                "\n\t",
+                r#"<key id="kind" for="edge" attr.name="kind" attr.type="string"/>"#,
+                "\n\t",
+                r#"<graph id="sweater" edgedefault="directed">"#
+            )
+            .to_string(),
+            GraphExchangeFormat::GEXF => concat!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+                "\n",
+                r#"<gexf xmlns="http://gexf.net/1.3" version="1.3">"#,
+                "\n\t",
+                r#"<graph defaultedgetype="directed">"#,
+                "\n\t\t",
+                r#"<attributes class="node">"#,
+                "\n\t\t\t",
+                r#"<attribute id="content" title="content" type="string"/>"#,
+                "\n\t\t\t",
+                r#"<attribute id="alias" title="alias" type="string"/>"#,
+                "\n\t\t\t",
+                r#"<attribute id="text" title="text" type="string"/>"#,
+                "\n\t\t\t",
+                r#"<attribute id="tags" title="tags" type="string"/>"#,
+                "\n\t\t",
+                r#"</attributes>"#,
+                "\n\t\t",
+                r#"<attributes class="edge">"#,
+                "\n\t\t\t",
+                r#"<attribute id="type" title="type" type="string"/>"#,
+                "\n\t\t\t",
+                r#"<attribute id="kind" title="kind" type="string"/>"#,
+                "\n\t\t",
+                r#"</attributes>"#,
+                "\n\t\t",
+                r#"<nodes>"#
+            )
+            .to_string(),
+        }
+    }
+
+    fn node_element(&self, node: &Node) -> String {
+        let mut attributes = vec![("content", node.content.to_string())];
+        if let Some(ref alias) = node.alias {
+            attributes.push(("alias", alias.clone()));
+        }
+        attributes.push(("text", node.text.clone()));
+        if !node.tags.is_empty() {
+            attributes.push(("tags", node.tags.join(" ")));
+        }
+        let label = node.alias.as_ref().unwrap_or(&node.id);
+        match self.config.format {
+            GraphExchangeFormat::GraphML => format!(
+                "\n\t\t<node id=\"{}\">{}\n\t\t</node>",
+                html_escape::encode_double_quoted_attribute(&node.id),
+                attributes
+                    .iter()
+                    .map(|(key, value)| format!(
+                        "\n\t\t\t<data key=\"{key}\">{}</data>",
+                        html_escape::encode_text(value)
+                    ))
+                    .collect::<Vec<_>>()
+                    .join("")
+            ),
+            GraphExchangeFormat::GEXF => format!(
+                "\n\t\t\t<node id=\"{}\" label=\"{}\">\n\t\t\t\t<attvalues>{}\n\t\t\t\t\
+                 </attvalues>\n\t\t\t</node>",
+                html_escape::encode_double_quoted_attribute(&node.id),
+                html_escape::encode_double_quoted_attribute(label),
+                attributes
+                    .iter()
+                    .map(|(key, value)| format!(
+                        "\n\t\t\t\t\t<attvalue for=\"{key}\" value=\"{}\"/>",
+                        html_escape::encode_double_quoted_attribute(value)
+                    ))
+                    .collect::<Vec<_>>()
+                    .join("")
+            ),
+        }
+    }
+
+    fn edge_element(&self, edge: &Edge) -> String {
+        let mut attributes = vec![("type", edge.edge_type.to_string())];
+        if let Some(ref kind) = edge.kind {
+            attributes.push(("kind", kind.clone()));
+        }
+        match self.config.format {
+            GraphExchangeFormat::GraphML => format!(
+                "\n\t\t<edge id=\"{}\" source=\"{}\" target=\"{}\">{}\n\t\t</edge>",
+                edge.id,
+                edge.source,
+                edge.target,
+                attributes
+                    .iter()
+                    .map(|(key, value)| format!(
+                        "\n\t\t\t<data key=\"{key}\">{}</data>",
+                        html_escape::encode_text(value)
+                    ))
+                    .collect::<Vec<_>>()
+                    .join("")
+            ),
+            GraphExchangeFormat::GEXF => format!(
+                "\n\t\t\t<edge id=\"{}\" source=\"{}\" target=\"{}\"{}>\n\t\t\t\t<attvalues>{}\
+                 \n\t\t\t\t</attvalues>\n\t\t\t</edge>",
+                edge.id,
+                edge.source,
+                edge.target,
+                if let Some(ref kind) = edge.kind {
+                    format!(
+                        " label=\"{}\"",
+                        html_escape::encode_double_quoted_attribute(kind)
+                    )
+                } else {
+                    String::new()
+                },
+                attributes
+                    .iter()
+                    .map(|(key, value)| format!(
+                        "\n\t\t\t\t\t<attvalue for=\"{key}\" value=\"{}\"/>",
+                        html_escape::encode_double_quoted_attribute(value)
+                    ))
+                    .collect::<Vec<_>>()
+                    .join("")
+            ),
+        }
+    }
+}
+
+impl<'a> FallibleIterator for GraphExchangeGenerator<'a> {
+    type Item = String;
+    type Error = Error;
+
+    fn next(&mut self) -> Result<Option<Self::Item>> {
+        Ok(match self.stage {
+            Stage::BeforeNodes => {
+                self.stage = Stage::Nodes;
+                Some(self.header())
+            }
+            Stage::Nodes => {
+                while let Some(thesis) = self.theses_iterator.next()? {
+                    if let Some(node) = self.node(&thesis)? {
+                        return Ok(Some(self.node_element(&node)));
+                    }
+                }
+                self.stage = Stage::Edges;
+                self.theses_iterator = Box::new(self.read_able_transaction.iter_theses()?);
+                match self.config.format {
+                    GraphExchangeFormat::GraphML => Some(String::new()),
+                    GraphExchangeFormat::GEXF => Some("\n\t\t</nodes>\n\t\t<edges>".to_string()),
+                }
+            }
+            Stage::Edges => {
+                if let Some(thesis) = self.theses_iterator.next()? {
+                    Some(
+                        self.edges(&thesis)?
+                            .iter()
+                            .map(|edge| self.edge_element(edge))
+                            .collect::<Vec<_>>()
+                            .join(""),
+                    )
+                } else {
+                    self.stage = Stage::Finished;
+                    Some(match self.config.format {
+                        GraphExchangeFormat::GraphML => "\n\t</graph>\n</graphml>\n".to_string(),
+                        GraphExchangeFormat::GEXF => {
+                            "\n\t\t</edges>\n\t</graph>\n</gexf>\n".to_string()
+                        }
+                    })
+                }
+            }
+            Stage::Finished => None,
+        })
+    }
+}
@@ -1,6 +1,9 @@
+use std::collections::BTreeMap;
+
 use anyhow::{Error, Result};
 use fallible_iterator::FallibleIterator;
 use serde::{Deserialize, Serialize};
+use trove::ObjectId;
 
 use crate::content::Content;
 use crate::read_transaction::ReadTransactionMethods;
@@ -20,11 +23,20 @@ pub enum ShowNodesReferences {
     All,
 }
 
+#[derive(PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum GraphOrder {
+    #[default]
+    Storage,
+    Deterministic,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GraphGeneratorConfig {
     pub wrap_width: u16,
     pub externalize_relations_nodes: ExternalizeRelationsNodes,
     pub show_nodes_references: ShowNodesReferences,
+    #[serde(default)]
+    pub order: GraphOrder,
 }
 
 pub enum Stage {
@@ -37,7 +49,9 @@ pub struct GraphGenerator<'a> {
     pub config: &'a GraphGeneratorConfig,
     pub read_able_transaction: &'a dyn ReadTransactionMethods<'a>,
     pub theses_iterator: Box<dyn FallibleIterator<Item = Thesis, Error = Error> + 'a>,
+    pub theses_names: BTreeMap<ObjectId, String>,
     pub stage: Stage,
+    pub edges_definitions: Vec<String>,
 }
 
 impl<'a> GraphGenerator<'a> {
@@ -48,55 +62,135 @@ impl<'a> GraphGenerator<'a> {
         Ok(Self {
             config,
             read_able_transaction,
-            theses_iterator: Box::new(read_able_transaction.iter_theses()?),
+            theses_iterator: match config.order {
+                GraphOrder::Storage => Box::new(read_able_transaction.iter_theses()?),
+                GraphOrder::Deterministic => {
+                    let mut theses = read_able_transaction
+                        .iter_theses()?
+                        .map(|thesis| {
+                            Ok((
+                                thesis.alias.is_none(),
+                                thesis.alias.clone(),
+                                read_able_transaction.thesis_id(&thesis)?,
+                                thesis,
+                            ))
+                        })
+                        .collect::<Vec<_>>()?;
+                    theses.sort_by(|a, b| (a.0, &a.1, &a.2).cmp(&(b.0, &b.1, &b.2)));
+                    Box::new(fallible_iterator::convert(
+                        theses.into_iter().map(|(_, _, _, thesis)| Ok(thesis)),
+                    ))
+                }
+            },
+            theses_names: read_able_transaction.get_theses_names()?,
             stage: Stage::BeforeFirstLine,
+            edges_definitions: Vec::new(),
         })
     }
 }
 
+pub fn wrap_lines(text: &str, wrap_width: usize) -> Vec<String> {
+    let mut result = Vec::new();
+    if wrap_width == 0 {
+        return result;
+    }
+
+    let mut current_line = String::new();
+    let mut current_line_size = 0;
+
+    for word in text.split_whitespace() {
+        let word_size = word.len();
+
+        if current_line.is_empty() {
+            current_line.reserve(word_size);
+            current_line.push_str(word);
+            current_line_size = word_size;
+        } else if current_line_size + 1 + word_size <= wrap_width {
+            current_line.push(' ');
+            current_line.push_str(word);
+            current_line_size += 1 + word_size;
+        } else {
+            result.push(current_line);
+            current_line = String::with_capacity(word_size);
+            current_line.push_str(word);
+            current_line_size = word_size;
+        }
+    }
+
+    if !current_line.is_empty() {
+        result.push(current_line);
+    }
+
+    result
+}
+
 impl<'a> GraphGenerator<'a> {
     fn wrap(&self, text: &str) -> String {
-        let wrap_width = self.config.wrap_width as usize;
-        if wrap_width == 0 {
-            return String::new();
-        }
+        wrap_lines(text, self.config.wrap_width as usize).join("<br/>")
+    }
 
-        let mut result = String::with_capacity(text.len() + (text.len() / wrap_width) * 5);
-        let mut current_line = String::new();
-        let mut current_line_size = 0;
-        let mut first_line = true;
-
-        for word in text.split_whitespace() {
-            let word_size = word.len();
-
-            if current_line.is_empty() {
-                current_line.reserve(word_size);
-                current_line.push_str(word);
-                current_line_size = word_size;
-            } else if current_line_size + 1 + word_size <= wrap_width {
-                current_line.push(' ');
-                current_line.push_str(word);
-                current_line_size += 1 + word_size;
-            } else {
-                if !first_line {
-                    result.push_str("<br/>");
-                }
-                result.push_str(&current_line);
-                first_line = false;
-                current_line = String::with_capacity(word_size);
-                current_line.push_str(word);
-                current_line_size = word_size;
+    fn definitions(&self, thesis: &Thesis) -> Result<(String, Vec<String>)> {
+        let thesis_id = self.read_able_transaction.thesis_id(thesis)?;
+        let thesis_id_string = thesis_id.to_string();
+        let node_header_text = html_escape::encode_text(
+            self.theses_names
+                .get(&thesis_id)
+                .unwrap_or(&thesis_id_string),
+        )
+        .to_string();
+        Ok(match thesis.content {
+            Content::Text(ref text) => {
+                let node_body_text = self.wrap(&text.composed_with_names(&self.theses_names));
+                let node_header =
+                    format!(r#"<TR><TD BORDER="1" SIDES="b">{node_header_text}</TD></TR>"#,);
+                let node_label = format!(
+                    r#"<TABLE BORDER="2" CELLSPACING="0" CELLPADDING="8">{}<TR><TD BORDER="0">{}</TD></TR></TABLE>"#,
+                    node_header, node_body_text
+                );
+                (
+                    format!(
+                        "\n\t\"{}\" [label=<{}>, shape=plaintext];", // node definition
+                        thesis_id_string, node_label
+                    ),
+                    thesis // node references arrows definitions
+                        .references()
+                        .iter()
+                        .map(|referenced_thesis_id| {
+                            format!(
+                                "\n\t\"{thesis_id_string}\" -> \"{}\" \
+                                 [arrowhead=none, color=\"grey\" style=dotted];",
+                                referenced_thesis_id.to_string()
+                            )
+                        })
+                        .collect(),
+                )
             }
-        }
-
-        if !current_line.is_empty() {
-            if !first_line {
-                result.push_str("<br/>");
+            Content::Relation(ref relation) => {
+                let node_label = format!(
+                    r#"<TABLE CELLSPACING="0" CELLPADDING="8" STYLE="dashed"><TR><TD SIDES="b" STYLE="dashed">{node_header_text}</TD></TR><TR><TD BORDER="0">{}</TD></TR></TABLE>"#,
+                    html_escape::encode_text(
+                        self.read_able_transaction
+                            .sweater_config()
+                            .exported_relation_kind(&relation.kind)
+                    )
+                );
+                (
+                    format!("\n\t\"{thesis_id_string}\" [label=<{node_label}>, shape=plaintext];"),
+                    vec![
+                        format!(
+                            "\n\t\"{}\" -> \"{}\" [dir=back, arrowtail=tee];",
+                            relation.from.to_string(), // arrow to relation node
+                            thesis_id_string
+                        ),
+                        format!(
+                            "\n\t\"{}\" -> \"{}\";",
+                            thesis_id_string, // arrow from relation node
+                            relation.to.to_string()
+                        ),
+                    ],
+                )
             }
-            result.push_str(&current_line);
-        }
-
-        result
+        })
     }
 }
 
@@ -112,60 +206,18 @@ impl<'a> FallibleIterator for GraphGenerator<'a> {
             }
             Stage::Middle => {
                 if let Some(thesis) = self.theses_iterator.next()? {
-                    let thesis_id_string = thesis.id()?.to_string();
-                    let node_header_text = if let Some(ref alias) = thesis.alias {
-                        html_escape::encode_text(&alias.0).to_string()
-                    } else {
-                        thesis_id_string.clone()
-                    };
-                    match thesis.content {
-                        Content::Text(ref text) => {
-                            let node_body_text =
-                                self.wrap(&text.composed_with_aliases(self.read_able_transaction)?);
-                            let node_header = format!(
-                                r#"<TR><TD BORDER="1" SIDES="b">{node_header_text}</TD></TR>"#,
-                            );
-                            let node_label = format!(
-                                r#"<TABLE BORDER="2" CELLSPACING="0" CELLPADDING="8">{}<TR><TD BORDER="0">{}</TD></TR></TABLE>"#,
-                                node_header, node_body_text
-                            );
-                            Some(
-                                format!(
-                                    "\n\t\"{}\" [label=<{}>, shape=plaintext];", // node definition
-                                    thesis_id_string, node_label
-                                ) + &thesis // node references arrows definitions
-                                    .references()
-                                    .iter()
-                                    .map(|referenced_thesis_id| {
-                                        format!(
-                                            "\n\t\"{thesis_id_string}\" -> \"{}\" \
-                                             [arrowhead=none, color=\"grey\" style=dotted];",
-                                            referenced_thesis_id.to_string()
-                                        )
-                                    })
-                                    .collect::<Vec<_>>()
-                                    .join(""),
-                            )
-                        }
-                        Content::Relation(ref relation) => {
-                            let node_label = format!(
-                                r#"<TABLE CELLSPACING="0" CELLPADDING="8" STYLE="dashed"><TR><TD SIDES="b" STYLE="dashed">{node_header_text}</TD></TR><TR><TD BORDER="0">{}</TD></TR></TABLE>"#,
-                                relation.kind.0
-                            );
-                            Some(format!(
-                                "\n\t\"{thesis_id_string}\" [label=<{node_label}>, \
-                                 shape=plaintext];\n\t\"{}\" -> \"{}\" [dir=back, \
-                                 arrowtail=tee];\n\t\"{}\" -> \"{}\";",
-                                relation.from.to_string(), // arrow to relation node
-                                thesis_id_string,
-                                thesis_id_string, // arrow from relation node
-                                relation.to.to_string()
-                            ))
+                    let (node_definition, edges_definitions) = self.definitions(&thesis)?;
+                    match self.config.order {
+                        GraphOrder::Storage => Some(node_definition + &edges_definitions.concat()),
+                        GraphOrder::Deterministic => {
+                            self.edges_definitions.extend(edges_definitions);
+                            Some(node_definition)
                         }
                     }
                 } else {
                     self.stage = Stage::AfterLastLine;
-                    Some("\n}".to_string())
+                    self.edges_definitions.sort();
+                    Some(std::mem::take(&mut self.edges_definitions).concat() + "\n}")
                 }
             }
             Stage::AfterLastLine => None,
@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IdSchemeVersion {
+    // text is encoded as it's composed form, relation is encoded with bincode standard
+    // configuration, kept for chests created before id schemes were introduced
+    #[default]
+    V1,
+    // canonical encoding not depending on any serialization library, see Content::encoded
+    V2,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IdHash {
+    #[default]
+    Xxh3,
+    Sha256,
+    Blake3,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IdScheme {
+    #[serde(default)]
+    pub version: IdSchemeVersion,
+    #[serde(default)]
+    pub hash: IdHash,
+}
+
+impl IdScheme {
+    // object identifiers are 128 bits long, so cryptographic hashes are truncated to their first
+    // 16 bytes
+    pub fn hash(&self, source: &[u8]) -> [u8; 16] {
+        match self.hash {
+            IdHash::Xxh3 => xxhash_rust::xxh3::xxh3_128(source).to_be_bytes(),
+            IdHash::Sha256 => sha2::Sha256::digest(source)[..16].try_into().unwrap(),
+            IdHash::Blake3 => blake3::hash(source).as_bytes()[..16].try_into().unwrap(),
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LayoutConfig {
+    pub nodes_separation: f64,
+    pub layers_separation: f64,
+    pub ordering_sweeps: usize,
+    pub positioning_iterations: usize,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            nodes_separation: 32.0,
+            layers_separation: 64.0,
+            ordering_sweeps: 8,
+            positioning_iterations: 16,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Size {
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+pub struct Layout {
+    pub width: f64,
+    pub height: f64,
+    pub nodes_centers: Vec<Point>,
+    pub edges_paths: Vec<Vec<Point>>,
+}
+
+struct Neighbours {
+    upper: Vec<Vec<usize>>,
+    lower: Vec<Vec<usize>>,
+}
+
+struct LayeredNode {
+    size: Size,
+    layer: usize,
+    order: usize,
+    x: f64,
+}
+
+// Sugiyama-style layered layout: cycles are broken by reversing DFS back edges, layers are
+// assigned by longest path, long edges are split with zero sized dummy nodes, layers are ordered
+// with barycenter sweeps and nodes are then pulled towards their neighbours horizontally
+impl Layout {
+    pub fn new(nodes_sizes: &[Size], edges: &[(usize, usize)], config: &LayoutConfig) -> Self {
+        let acyclic_edges = Self::acyclic(nodes_sizes.len(), edges);
+        let layers_indices = Self::layers_indices(nodes_sizes.len(), &acyclic_edges);
+
+        let mut nodes: Vec<LayeredNode> = nodes_sizes
+            .iter()
+            .zip(layers_indices.iter())
+            .map(|(size, layer)| LayeredNode {
+                size: *size,
+                layer: *layer,
+                order: 0,
+                x: 0.0,
+            })
+            .collect();
+        let mut chains: Vec<Vec<usize>> = Vec::with_capacity(edges.len());
+        for (from, to) in acyclic_edges.iter() {
+            let mut chain = vec![*from];
+            for layer in nodes[*from].layer + 1..nodes[*to].layer {
+                nodes.push(LayeredNode {
+                    size: Size {
+                        width: 0.0,
+                        height: 0.0,
+                    },
+                    layer,
+                    order: 0,
+                    x: 0.0,
+                });
+                chain.push(nodes.len() - 1);
+            }
+            chain.push(*to);
+            chains.push(chain);
+        }
+
+        let mut neighbours = Neighbours {
+            upper: vec![Vec::new(); nodes.len()],
+            lower: vec![Vec::new(); nodes.len()],
+        };
+        for chain in chains.iter() {
+            for pair in chain.windows(2) {
+                if pair[0] != pair[1] {
+                    neighbours.lower[pair[0]].push(pair[1]);
+                    neighbours.upper[pair[1]].push(pair[0]);
+                }
+            }
+        }
+
+        let layers_count = nodes.iter().map(|node| node.layer + 1).max().unwrap_or(0);
+        let mut layers: Vec<Vec<usize>> = vec![Vec::new(); layers_count];
+        for (node_index, node) in nodes.iter().enumerate() {
+            layers[node.layer].push(node_index);
+        }
+        Self::order(&mut nodes, &mut layers, &neighbours, config);
+        Self::position(&mut nodes, &layers, &neighbours, config);
+
+        let mut layers_y = Vec::with_capacity(layers.len());
+        let mut y = config.layers_separation / 2.0;
+        for layer in layers.iter() {
+            let layer_height = layer
+                .iter()
+                .map(|node_index| nodes[*node_index].size.height)
+                .fold(0.0, f64::max);
+            layers_y.push(y + layer_height / 2.0);
+            y += layer_height + config.layers_separation;
+        }
+        let height = y - config.layers_separation / 2.0;
+        let width = nodes
+            .iter()
+            .map(|node| node.x + node.size.width / 2.0)
+            .fold(0.0, f64::max)
+            + config.nodes_separation / 2.0;
+
+        let center = |node_index: usize| Point {
+            x: nodes[node_index].x,
+            y: layers_y[nodes[node_index].layer],
+        };
+        let edges_paths = edges
+            .iter()
+            .zip(chains.iter())
+            .map(|((from, _), chain)| {
+                let mut path: Vec<Point> =
+                    chain.iter().map(|node_index| center(*node_index)).collect();
+                if chain[0] != *from {
+                    path.reverse();
+                }
+                path
+            })
+            .collect();
+
+        Self {
+            width,
+            height,
+            nodes_centers: (0..nodes_sizes.len()).map(center).collect(),
+            edges_paths,
+        }
+    }
+
+    fn acyclic(nodes_count: usize, edges: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        let mut outgoing: Vec<Vec<usize>> = vec![Vec::new(); nodes_count];
+        for (edge_index, (from, _)) in edges.iter().enumerate() {
+            outgoing[*from].push(edge_index);
+        }
+        // 0 is not visited, 1 is on stack, 2 is finished
+        let mut states = vec![0u8; nodes_count];
+        let mut reversed = vec![false; edges.len()];
+        for root in 0..nodes_count {
+            if states[root] != 0 {
+                continue;
+            }
+            let mut stack = vec![(root, 0)];
+            states[root] = 1;
+            while let Some((node, next_edge_position)) = stack.pop() {
+                if let Some(edge_index) = outgoing[node].get(next_edge_position) {
+                    stack.push((node, next_edge_position + 1));
+                    let to = edges[*edge_index].1;
+                    match states[to] {
+                        0 => {
+                            states[to] = 1;
+                            stack.push((to, 0));
+                        }
+                        1 => reversed[*edge_index] = true,
+                        _ => {}
+                    }
+                } else {
+                    states[node] = 2;
+                }
+            }
+        }
+        edges
+            .iter()
+            .zip(reversed.iter())
+            .map(|((from, to), reversed)| {
+                if *reversed {
+                    (*to, *from)
+                } else {
+                    (*from, *to)
+                }
+            })
+            .collect()
+    }
+
+    fn layers_indices(nodes_count: usize, acyclic_edges: &[(usize, usize)]) -> Vec<usize> {
+        let mut incoming_count = vec![0usize; nodes_count];
+        let mut outgoing: Vec<Vec<usize>> = vec![Vec::new(); nodes_count];
+        for (from, to) in acyclic_edges.iter() {
+            if from != to {
+                incoming_count[*to] += 1;
+                outgoing[*from].push(*to);
+            }
+        }
+        let mut result = vec![0usize; nodes_count];
+        let mut queue: std::collections::VecDeque<usize> = (0..nodes_count)
+            .filter(|node_index| incoming_count[*node_index] == 0)
+            .collect();
+        while let Some(node_index) = queue.pop_front() {
+            for to in outgoing[node_index].iter() {
+                result[*to] = result[*to].max(result[node_index] + 1);
+                incoming_count[*to] -= 1;
+                if incoming_count[*to] == 0 {
+                    queue.push_back(*to);
+                }
+            }
+        }
+        result
+    }
+
+    fn order(
+        nodes: &mut [LayeredNode],
+        layers: &mut [Vec<usize>],
+        neighbours: &Neighbours,
+        config: &LayoutConfig,
+    ) {
+        for layer in layers.iter() {
+            for (order, node_index) in layer.iter().enumerate() {
+                nodes[*node_index].order = order;
+            }
+        }
+        for sweep in 0..config.ordering_sweeps {
+            let downwards = sweep % 2 == 0;
+            let layers_indices: Vec<usize> = if downwards {
+                (1..layers.len()).collect()
+            } else {
+                (0..layers.len().saturating_sub(1)).rev().collect()
+            };
+            for layer_index in layers_indices {
+                let barycenters: Vec<(usize, f64)> = layers[layer_index]
+                    .iter()
+                    .map(|node_index| {
+                        let fixed_neighbours = if downwards {
+                            &neighbours.upper[*node_index]
+                        } else {
+                            &neighbours.lower[*node_index]
+                        };
+                        (
+                            *node_index,
+                            if fixed_neighbours.is_empty() {
+                                nodes[*node_index].order as f64
+                            } else {
+                                fixed_neighbours
+                                    .iter()
+                                    .map(|neighbour_index| nodes[*neighbour_index].order as f64)
+                                    .sum::<f64>()
+                                    / fixed_neighbours.len() as f64
+                            },
+                        )
+                    })
+                    .collect();
+                let mut sorted = barycenters;
+                sorted.sort_by(|a, b| a.1.total_cmp(&b.1));
+                layers[layer_index] = sorted.iter().map(|(node_index, _)| *node_index).collect();
+                for (order, node_index) in layers[layer_index].iter().enumerate() {
+                    nodes[*node_index].order = order;
+                }
+            }
+        }
+    }
+
+    fn position(
+        nodes: &mut [LayeredNode],
+        layers: &[Vec<usize>],
+        neighbours: &Neighbours,
+        config: &LayoutConfig,
+    ) {
+        for layer in layers.iter() {
+            let mut x = config.nodes_separation / 2.0;
+            for node_index in layer.iter() {
+                nodes[*node_index].x = x + nodes[*node_index].size.width / 2.0;
+                x += nodes[*node_index].size.width + config.nodes_separation;
+            }
+        }
+        for _ in 0..config.positioning_iterations {
+            for layer in layers.iter() {
+                let desired: Vec<f64> = layer
+                    .iter()
+                    .map(|node_index| {
+                        let all_neighbours = neighbours.upper[*node_index]
+                            .iter()
+                            .chain(neighbours.lower[*node_index].iter());
+                        let neighbours_count = neighbours.upper[*node_index].len()
+                            + neighbours.lower[*node_index].len();
+                        if neighbours_count == 0 {
+                            nodes[*node_index].x
+                        } else {
+                            all_neighbours
+                                .map(|neighbour_index| nodes[*neighbour_index].x)
+                                .sum::<f64>()
+                                / neighbours_count as f64
+                        }
+                    })
+                    .collect();
+                let mut minimal_x = config.nodes_separation / 2.0;
+                for (node_index, desired_x) in layer.iter().zip(desired.iter()) {
+                    let half_width = nodes[*node_index].size.width / 2.0;
+                    nodes[*node_index].x = desired_x.max(minimal_x + half_width);
+                    minimal_x = nodes[*node_index].x + half_width + config.nodes_separation;
+                }
+            }
+        }
+        let leftmost = nodes
+            .iter()
+            .map(|node| node.x - node.size.width / 2.0)
+            .fold(f64::INFINITY, f64::min);
+        if leftmost.is_finite() {
+            let shift = config.nodes_separation / 2.0 - leftmost;
+            for node in nodes.iter_mut() {
+                node.x += shift;
+            }
+        }
+    }
+}
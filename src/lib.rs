@@ -1,14 +1,26 @@
 pub mod alias;
 pub mod aliases_resolver;
+pub mod analysis;
 pub mod commands;
 pub mod content;
+pub mod formatter;
+pub mod graph_exchange_generator;
 pub mod graph_generator;
+pub mod id_scheme;
+pub mod layout;
+pub mod lints;
+pub mod migration;
+pub mod paragraphs;
 pub mod read_transaction;
 pub mod relation;
+pub mod site_generator;
+pub mod svg_generator;
 pub mod sweater;
+pub mod sync;
 pub mod tag;
 pub mod text;
 pub mod thesis;
+pub mod vault;
 pub mod write_transaction;
 
 use trove::PathSegment;
@@ -22,19 +34,34 @@ mod tests {
     use pretty_assertions::assert_eq;
     use trove::ObjectId;
 
+    use crate::alias::Alias;
     use crate::aliases_resolver::AliasesResolver;
-    use crate::commands::CommandsIterator;
+    use crate::analysis::{Analysis, Completion, Position};
+    use crate::commands::{Command, CommandsIterator, Reference};
     use crate::content::Content;
+    use crate::formatter;
+    use crate::graph_exchange_generator::{
+        GraphExchangeFormat, GraphExchangeGenerator, GraphExchangeGeneratorConfig,
+    };
     use crate::graph_generator::{
-        ExternalizeRelationsNodes, GraphGenerator, GraphGeneratorConfig, ShowNodesReferences,
+        ExternalizeRelationsNodes, GraphGenerator, GraphGeneratorConfig, GraphOrder,
+        ShowNodesReferences,
     };
+    use crate::id_scheme::{IdHash, IdScheme, IdSchemeVersion};
+    use crate::layout::{Layout, LayoutConfig, Point, Size};
+    use crate::lints::{self, LintKind, LintsConfig};
+    use crate::migration;
     use crate::read_transaction::ReadTransactionMethods;
-    use crate::relation::Relation;
-    use crate::sweater::Sweater;
+    use crate::relation::{Relation, RelationKind, RelationKindForm};
+    use crate::site_generator::{SiteGenerator, SiteGeneratorConfig};
+    use crate::svg_generator::SvgGeneratorConfig;
+    use crate::sweater::{Sweater, SweaterConfig};
+    use crate::sync;
     use crate::tag::Tag;
-    use crate::text::Text;
+    use crate::text::{RawText, Text, TextCharset};
     use crate::thesis::Thesis;
-    use crate::write_transaction::WriteTransaction;
+    use crate::vault::{VaultExporter, VaultImporter};
+    use crate::write_transaction::{ApplyMode, WriteTransaction};
 
     fn new_default_sweater(test_name_for_isolation: &str) -> Sweater {
         Sweater::new(
@@ -97,7 +124,11 @@ mod tests {
             .collect();
         let mut result_string = String::new();
         for (i, word) in words.iter().enumerate() {
-            result_string.push_str(word);
+            if rng.generate_range(0..8) == 0 {
+                result_string.push_str(&format!("\\[{word}\\]"));
+            } else {
+                result_string.push_str(word);
+            }
             if i < words.len() - 1 {
                 if rng.generate_range(0..3) == 0 {
                     result_string.push_str(PUNCTUATION[rng.generate_range(0..PUNCTUATION.len())]);
@@ -194,7 +225,9 @@ mod tests {
                                     &previously_added_theses,
                                     &transaction,
                                 );
-                                while previously_added_theses.contains_key(&result.id()?) {
+                                while previously_added_theses
+                                    .contains_key(&transaction.thesis_id(&result)?)
+                                {
                                     result = random_thesis(
                                         &mut rng,
                                         &mut aliases_resolver,
@@ -204,9 +237,9 @@ mod tests {
                                 }
                                 result
                             };
-                            thesis.validated()?;
+                            thesis.validated(&transaction.sweater_config.text_charset)?;
                             transaction.insert_thesis(thesis.clone())?;
-                            let thesis_id = thesis.id()?;
+                            let thesis_id = transaction.thesis_id(&thesis)?;
                             assert_eq!(transaction.get_thesis(&thesis_id)?.unwrap(), thesis);
                             for referenced_thesis_id in thesis.references() {
                                 let where_referenced =
@@ -294,13 +327,1920 @@ mod tests {
                             wrap_width: 64,
                             externalize_relations_nodes: ExternalizeRelationsNodes::None,
                             show_nodes_references: ShowNodesReferences::All,
+                            order: GraphOrder::Storage,
                         },
                         transaction,
                     )?
                     .collect::<Vec<_>>()?
                     .join(""),
                 )?;
+                let mut expected_nodes_ids = std::collections::BTreeSet::new();
+                let mut expected_edges_count = 0;
+                let mut theses = transaction.iter_theses()?;
+                while let Some(thesis) = theses.next()? {
+                    let thesis_id = transaction.thesis_id(&thesis)?;
+                    match thesis.content {
+                        Content::Text(ref text) => {
+                            expected_nodes_ids.insert(thesis_id.to_string());
+                            expected_edges_count += text.references.len();
+                        }
+                        Content::Relation(_) => {
+                            if !transaction.where_referenced(&thesis_id)?.is_empty() {
+                                expected_nodes_ids.insert(thesis_id.to_string());
+                            }
+                            expected_edges_count += 1;
+                        }
+                    }
+                }
+                drop(theses);
+                let node_regex = regex::Regex::new(r#"<node id="([^"]+)""#)?;
+                let edge_regex =
+                    regex::Regex::new(r#"<edge id="[^"]+" source="([^"]+)" target="([^"]+)""#)?;
+                for format in [GraphExchangeFormat::GraphML, GraphExchangeFormat::GEXF] {
+                    let graph = GraphExchangeGenerator::new(
+                        &GraphExchangeGeneratorConfig { format },
+                        transaction,
+                    )?
+                    .collect::<Vec<_>>()?
+                    .join("");
+                    let nodes_ids = node_regex
+                        .captures_iter(&graph)
+                        .map(|captures| captures[1].to_string())
+                        .collect::<Vec<_>>();
+                    assert_eq!(nodes_ids.len(), expected_nodes_ids.len());
+                    assert_eq!(
+                        nodes_ids
+                            .into_iter()
+                            .collect::<std::collections::BTreeSet<_>>(),
+                        expected_nodes_ids
+                    );
+                    let edges = edge_regex.captures_iter(&graph).collect::<Vec<_>>();
+                    assert_eq!(edges.len(), expected_edges_count);
+                    assert!(edges.iter().all(|captures| {
+                        expected_nodes_ids.contains(&captures[1])
+                            && expected_nodes_ids.contains(&captures[2])
+                    }));
+                }
+
+                Ok(())
+            })
+            .unwrap();
+        sweater
+            .render_svg(
+                &SvgGeneratorConfig {
+                    wrap_width: 64,
+                    font_size: 14.0,
+                    layout: LayoutConfig::default(),
+                },
+                std::path::Path::new("/tmp/woollib_example_graph.svg"),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_layout() {
+        let config = LayoutConfig::default();
+        let size = |width: f64, height: f64| Size { width, height };
+
+        let layout = Layout::new(&[size(100.0, 20.0), size(100.0, 20.0)], &[(0, 1)], &config);
+        assert_eq!(
+            layout.nodes_centers,
+            [Point { x: 66.0, y: 42.0 }, Point { x: 66.0, y: 126.0 }]
+        );
+        assert_eq!((layout.width, layout.height), (132.0, 168.0));
+        assert_eq!(layout.edges_paths, [layout.nodes_centers.clone()]);
+
+        let nodes_sizes = [
+            size(120.0, 40.0),
+            size(80.0, 20.0),
+            size(60.0, 60.0),
+            size(100.0, 30.0),
+            size(40.0, 20.0),
+            size(90.0, 50.0),
+        ];
+        let edges = [
+            (0, 1),
+            (0, 2),
+            (1, 3),
+            (2, 3),
+            (3, 0),
+            (0, 3),
+            (4, 5),
+            (2, 2),
+        ];
+        let layout = Layout::new(&nodes_sizes, &edges, &config);
+        assert_eq!(layout.nodes_centers.len(), nodes_sizes.len());
+        for first in 0..nodes_sizes.len() {
+            for second in first + 1..nodes_sizes.len() {
+                let (a, b) = (layout.nodes_centers[first], layout.nodes_centers[second]);
+                let overlap_x = (a.x - b.x).abs()
+                    < (nodes_sizes[first].width + nodes_sizes[second].width) / 2.0;
+                let overlap_y = (a.y - b.y).abs()
+                    < (nodes_sizes[first].height + nodes_sizes[second].height) / 2.0;
+                assert!(!(overlap_x && overlap_y), "{first} overlaps {second}");
+            }
+        }
+        for (node_index, center) in layout.nodes_centers.iter().enumerate() {
+            assert!(center.x - nodes_sizes[node_index].width / 2.0 >= 0.0);
+            assert!(center.x + nodes_sizes[node_index].width / 2.0 <= layout.width);
+            assert!(center.y - nodes_sizes[node_index].height / 2.0 >= 0.0);
+            assert!(center.y + nodes_sizes[node_index].height / 2.0 <= layout.height);
+        }
+        for ((from, to), path) in edges.iter().zip(layout.edges_paths.iter()) {
+            assert_eq!(path.first(), Some(&layout.nodes_centers[*from]));
+            assert_eq!(path.last(), Some(&layout.nodes_centers[*to]));
+        }
+        // long edge goes through dummy node on each intermediate layer
+        assert_eq!(layout.edges_paths[5].len(), 3);
+        for (from, to) in [(0, 1), (0, 2), (1, 3), (2, 3), (4, 5)] {
+            assert!(layout.nodes_centers[from].y < layout.nodes_centers[to].y);
+        }
+
+        let again = Layout::new(&nodes_sizes, &edges, &config);
+        assert_eq!(again.nodes_centers, layout.nodes_centers);
+        assert_eq!(again.edges_paths, layout.edges_paths);
+        assert_eq!((again.width, again.height), (layout.width, layout.height));
+    }
+
+    #[test]
+    fn test_deterministic_graph() {
+        let mut sweater = new_default_sweater("test_deterministic_graph");
+        let theses = sweater
+            .lock_all_and_write(|transaction| {
+                let commands = CommandsIterator::new(
+                    &std::fs::read_to_string("src/example.txt")?,
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut AliasesResolver {
+                        read_able_transaction: transaction,
+                        known_aliases: BTreeMap::new(),
+                    },
+                )
+                .collect::<Vec<_>>()?;
+                for command in commands {
+                    transaction.execute_command(&command)?;
+                }
+                transaction.iter_theses()?.collect::<Vec<_>>()
+            })
+            .unwrap();
+
+        // the same theses inserted in different order, latest ready thesis first
+        let mut reordered_sweater = new_default_sweater("test_deterministic_graph_reordered");
+        reordered_sweater
+            .lock_all_and_write(|transaction| {
+                let mut pending = theses.clone();
+                while !pending.is_empty() {
+                    let ready_index = pending
+                        .iter()
+                        .rposition(|thesis| {
+                            thesis.references().iter().all(|referenced_thesis_id| {
+                                matches!(transaction.get_thesis(referenced_thesis_id), Ok(Some(_)))
+                            })
+                        })
+                        .unwrap();
+                    transaction.insert_thesis(pending.remove(ready_index))?;
+                }
+                Ok(())
+            })
+            .unwrap();
+
+        let config = GraphGeneratorConfig {
+            wrap_width: 64,
+            externalize_relations_nodes: ExternalizeRelationsNodes::None,
+            show_nodes_references: ShowNodesReferences::All,
+            order: GraphOrder::Deterministic,
+        };
+        let graph = |sweater: &Sweater| {
+            sweater
+                .lock_all_writes_and_read(|transaction| {
+                    Ok(GraphGenerator::new(&config, &transaction)?
+                        .collect::<Vec<_>>()?
+                        .join(""))
+                })
+                .unwrap()
+        };
+        let graph_of_reordered = graph(&reordered_sweater);
+        assert_eq!(graph(&sweater), graph_of_reordered);
+        let lines = graph_of_reordered.lines().collect::<Vec<_>>();
+        let first_edge_index = lines.iter().position(|line| line.contains("->")).unwrap();
+        let edges = &lines[first_edge_index..lines.len() - 1];
+        assert!(edges.iter().all(|line| line.contains("->")));
+        assert!(edges.is_sorted());
+    }
+
+    #[test]
+    fn test_site() {
+        let mut sweater = new_default_sweater("test_site");
+        sweater
+            .lock_all_and_write(|transaction| {
+                let commands = CommandsIterator::new(
+                    &std::fs::read_to_string("src/example.txt")?,
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut AliasesResolver {
+                        read_able_transaction: transaction,
+                        known_aliases: BTreeMap::new(),
+                    },
+                )
+                .collect::<Vec<_>>()?;
+                for command in commands {
+                    transaction.execute_command(&command)?;
+                }
+                Ok(())
+            })
+            .unwrap();
+        sweater
+            .lock_all_writes_and_read(|transaction| {
+                let directory = std::path::Path::new("/tmp/woollib/test/test_site/site");
+                SiteGenerator::new(
+                    &SiteGeneratorConfig {
+                        title: "example".to_string(),
+                    },
+                    &transaction,
+                )?
+                .write(directory)?;
+                let thesis_id = transaction
+                    .get_thesis_id_by_alias(&Alias("(R-r).0".to_string()))?
+                    .unwrap();
+                let page = std::fs::read_to_string(
+                    directory.join(SiteGenerator::thesis_file_name(&thesis_id)),
+                )?;
+                let link = |alias: &str| -> anyhow::Result<String> {
+                    Ok(format!(
+                        "<a href=\"{}\">[{alias}]</a>",
+                        SiteGenerator::thesis_file_name(
+                            &transaction
+                                .get_thesis_id_by_alias(&Alias(alias.to_string()))?
+                                .unwrap()
+                        )
+                    ))
+                };
+                let (outgoing, rest) = page.split_once("<h2>Incoming relations</h2>").unwrap();
+                let (incoming, referenced_in) = rest.split_once("<h2>Referenced in</h2>").unwrap();
+                let may_be = outgoing.split_once("<h3>may be</h3>").unwrap().1;
+                assert!(may_be.contains(&format!("<li>{} (", link("(R-r).0_true_relatively")?)));
+                let therefore = incoming.split_once("<h3>therefore</h3>").unwrap().1;
+                assert!(therefore.contains(&format!("<li>{} (", link("(R-r).d")?)));
+                assert!(!incoming.contains("<h3>may be</h3>"));
+                assert!(referenced_in
+                    .contains(&format!("<li>{}</li>", link("(R-r).0_true_relatively")?)));
+                assert!(directory.join("index.html").exists());
+                assert!(directory.join("aliases.html").exists());
+                assert!(directory.join("tags.html").exists());
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_vault() {
+        let mut sweater = new_default_sweater("test_vault");
+        let directory = std::path::Path::new("/tmp/woollib/test/test_vault/vault");
+        let _ = std::fs::remove_dir_all(directory);
+        // "#" is allowed in text to check that last line of words starting with it is not tags
+        let charset = TextCharset::new(
+            vec!["Cyrillic".to_string(), "Latin".to_string()],
+            ",-:.'\"#".to_string(),
+        );
+        let exported = sweater
+            .lock_all_and_write(|transaction| {
+                transaction.sweater_config.text_charset = charset.clone();
+                let commands = CommandsIterator::new(
+                    &(std::fs::read_to_string("src/example.txt")?
+                        + "\n\n+ a/b #first\nПервый\n\n+ A_b\nВторой\n#не #теги"),
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut AliasesResolver {
+                        read_able_transaction: transaction,
+                        known_aliases: BTreeMap::new(),
+                    },
+                )
+                .collect::<Vec<_>>()?;
+                for command in commands {
+                    transaction.execute_command(&command)?;
+                }
+                VaultExporter::new(transaction)?.write(directory)?;
+                let theses = transaction
+                    .iter_theses()?
+                    .map(|thesis| Ok((transaction.thesis_id(&thesis)?, thesis)))
+                    .collect::<BTreeMap<_, _>>()?;
+                assert_eq!(std::fs::read_dir(directory)?.count(), theses.len());
+                for alias in ["a/b", "A_b"] {
+                    let thesis_id = transaction
+                        .get_thesis_id_by_alias(&Alias(alias.to_string()))?
+                        .unwrap();
+                    assert!(directory
+                        .join(format!("{}_b {thesis_id}.md", &alias[..1]))
+                        .exists());
+                }
+                let first_id = transaction
+                    .get_thesis_id_by_alias(&Alias("a/b".to_string()))?
+                    .unwrap();
+                assert!(
+                    std::fs::read_to_string(directory.join(format!("a_b {first_id}.md")))?
+                        .ends_with("\n---\nПервый\n\n#first\n")
+                );
+                Ok(theses)
+            })
+            .unwrap();
+
+        let mut imported_sweater = new_default_sweater("test_vault_imported");
+        let imported = imported_sweater
+            .lock_all_and_write(|transaction| {
+                transaction.sweater_config.text_charset = charset.clone();
+                let commands = VaultImporter::new(&mut AliasesResolver {
+                    read_able_transaction: transaction,
+                    known_aliases: BTreeMap::new(),
+                })
+                .commands(directory)?;
+                for command in commands {
+                    transaction.execute_command(&command)?;
+                }
+                transaction
+                    .iter_theses()?
+                    .map(|thesis| Ok((transaction.thesis_id(&thesis)?, thesis)))
+                    .collect::<BTreeMap<_, _>>()
+            })
+            .unwrap();
+        assert_eq!(imported, exported);
+    }
+
+    #[test]
+    fn test_comments() {
+        let mut sweater = new_default_sweater("test_comments");
+        sweater
+            .lock_all_and_write(|transaction| {
+                let parse = |input: &str| {
+                    CommandsIterator::new(
+                        input,
+                        &transaction.sweater_config.supported_relations_kinds,
+                        &mut AliasesResolver {
+                            read_able_transaction: transaction,
+                            known_aliases: BTreeMap::new(),
+                        },
+                    )
+                    .collect::<Vec<_>>()
+                };
+                assert_eq!(
+                    parse(
+                        "// theses about relativism\n+ R\n; the most general one\nРелятивизм\n\n\
+                         ; second one\n\n+ R-r\nПолный релятивизм\n// trailing comment"
+                    )?,
+                    parse("+ R\nРелятивизм\n\n+ R-r\nПолный релятивизм")?
+                );
+                let error = parse("// first comment\n// second comment\n\n+ R\nРелятивизм\n\n!\nR")
+                    .unwrap_err()
+                    .to_string();
+                assert!(error.contains("2-th paragraph at line 7"), "{error}");
+                assert!(parse("+ R\nРелятивизм\n  ; не комментарий").is_err());
+                assert!(parse("+ R\nРелятивизм\n;не комментарий").is_err());
+                assert!(
+                    TextCharset::new(vec!["Cyrillic".to_string()], ",;".to_string())
+                        .regex()
+                        .is_err()
+                );
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_includes() {
+        let directory = std::path::Path::new("/tmp/woollib/test/test_includes/commands");
+        std::fs::create_dir_all(directory.join("nested")).unwrap();
+        std::fs::write(
+            directory.join("main.txt"),
+            "include nested/definitions.txt\n\n+\nR\nmay be\nR-r\n\ninclude \
+             nested/definitions.txt",
+        )
+        .unwrap();
+        std::fs::write(
+            directory.join("nested/definitions.txt"),
+            "+ R\nРелятивизм\n\n+ R-r\nПолный релятивизм",
+        )
+        .unwrap();
+        std::fs::write(directory.join("cycle_a.txt"), "include cycle_b.txt").unwrap();
+        std::fs::write(directory.join("cycle_b.txt"), "include cycle_a.txt").unwrap();
+        std::fs::write(directory.join("broken.txt"), "include nested/broken.txt").unwrap();
+        std::fs::write(
+            directory.join("nested/broken.txt"),
+            "+ R\nРелятивизм\n\n!\nR",
+        )
+        .unwrap();
+        std::fs::write(
+            directory.join("scoped.txt"),
+            "namespace (R)\n\n+ .a\nПервый\n\ninclude nested/scoped.txt\n\n+ .b\nВторой",
+        )
+        .unwrap();
+        std::fs::write(
+            directory.join("nested/scoped.txt"),
+            "+ .c\nТретий\n\nnamespace (Q)\n\n+ .d\nЧетвёртый",
+        )
+        .unwrap();
+
+        let mut sweater = new_default_sweater("test_includes");
+        sweater
+            .lock_all_and_write(|transaction| {
+                let mut aliases_resolver = AliasesResolver {
+                    read_able_transaction: transaction,
+                    known_aliases: BTreeMap::new(),
+                };
+                let commands = CommandsIterator::from_file(
+                    &directory.join("main.txt"),
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut aliases_resolver,
+                )?
+                .collect::<Vec<_>>()?;
+                assert_eq!(commands.len(), 3);
+                assert!(aliases_resolver
+                    .known_aliases
+                    .contains_key(&Alias("R-r".to_string())));
+
+                let cycle_error = CommandsIterator::from_file(
+                    &directory.join("cycle_a.txt"),
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut aliases_resolver,
+                )?
+                .collect::<Vec<_>>()
+                .unwrap_err()
+                .to_string();
+                assert!(cycle_error.contains("include cycle"), "{cycle_error}");
+
+                let broken_error = CommandsIterator::from_file(
+                    &directory.join("broken.txt"),
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut aliases_resolver,
+                )?
+                .collect::<Vec<_>>()
+                .unwrap_err()
+                .to_string();
+                assert!(
+                    broken_error.contains("2-th paragraph at line 4 of file")
+                        && broken_error.contains("nested/broken.txt"),
+                    "{broken_error}"
+                );
+
+                let aliases = CommandsIterator::from_file(
+                    &directory.join("scoped.txt"),
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut aliases_resolver,
+                )?
+                .map(|command| {
+                    Ok(match command {
+                        Command::AddThesis(thesis) => thesis.alias.map(|alias| alias.0),
+                        _ => None,
+                    })
+                })
+                .collect::<Vec<_>>()?;
+                assert_eq!(
+                    aliases,
+                    [
+                        Some("(R).a".to_string()),
+                        Some(".c".to_string()),
+                        Some("(Q).d".to_string()),
+                        Some("(R).b".to_string())
+                    ]
+                );
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_inline_tags() {
+        let mut sweater = new_default_sweater("test_inline_tags");
+        sweater
+            .lock_all_and_write(|transaction| {
+                let mut aliases_resolver = AliasesResolver {
+                    read_able_transaction: transaction,
+                    known_aliases: BTreeMap::new(),
+                };
+                let commands = CommandsIterator::new(
+                    "+ R-r #total #draft\nПолный релятивизм\n\n+ #draft\nРелятивизм",
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut aliases_resolver,
+                )
+                .collect::<Vec<_>>()?;
+                let tags = commands
+                    .iter()
+                    .map(|command| match command {
+                        Command::AddThesis(thesis) => (thesis.alias.clone(), thesis.tags.clone()),
+                        _ => panic!(),
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    tags,
+                    vec![
+                        (
+                            Some(Alias("R-r".to_string())),
+                            vec![Tag("total".to_string()), Tag("draft".to_string())]
+                        ),
+                        (None, vec![Tag("draft".to_string())])
+                    ]
+                );
+                for invalid_input in ["+ R #not-a-tag\nРелятивизм", "- #total\nR-r"] {
+                    assert!(CommandsIterator::new(
+                        invalid_input,
+                        &transaction.sweater_config.supported_relations_kinds,
+                        &mut aliases_resolver,
+                    )
+                    .collect::<Vec<_>>()
+                    .is_err());
+                }
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_multiline_text() {
+        let mut sweater = new_default_sweater("test_multiline_text");
+        sweater
+            .lock_all_and_write(|transaction| {
+                let mut aliases_resolver = AliasesResolver {
+                    read_able_transaction: transaction,
+                    known_aliases: BTreeMap::new(),
+                };
+                let commands = CommandsIterator::new(
+                    "+ R-r\nПолный релятивизм\nвсё относительно\n\n+ R\nРелятивизм\n\n\
+                     +   many\nпервая строка\nвторая строка\nтретья строка\n\n+\nR-r\nmeans\nR",
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut aliases_resolver,
+                )
+                .collect::<Vec<_>>()?;
+                let contents = commands
+                    .iter()
+                    .map(|command| match command {
+                        Command::AddThesis(thesis) => thesis.content.clone(),
+                        _ => panic!(),
+                    })
+                    .collect::<Vec<_>>();
+                match &contents[0] {
+                    Content::Text(text) => {
+                        assert_eq!(text.composed(), "Полный релятивизм\nвсё относительно")
+                    }
+                    _ => panic!(),
+                }
+                match &contents[2] {
+                    Content::Text(text) => assert_eq!(
+                        text.composed(),
+                        "первая строка\nвторая строка\nтретья строка"
+                    ),
+                    _ => panic!(),
+                }
+                assert!(matches!(contents[3], Content::Relation(_)));
+                assert!(CommandsIterator::new(
+                    "+\nunknown\nmeans\nR",
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut aliases_resolver,
+                )
+                .collect::<Vec<_>>()
+                .is_err());
+                let error = CommandsIterator::new(
+                    "+\nR-r\nmight be\nR",
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut aliases_resolver,
+                )
+                .collect::<Vec<_>>()
+                .unwrap_err();
+                assert!(
+                    format!("{error:#}").contains("is not supported relation kind"),
+                    "{error:#}"
+                );
+                // the same lines shapes are parsed the same way whether references are known
+                assert!(CommandsIterator::new(
+                    "+\nunknown\nmight be\nother",
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut aliases_resolver,
+                )
+                .collect::<Vec<_>>()
+                .is_err());
+                assert!(matches!(
+                    &CommandsIterator::new(
+                        "+\nпервая строка\nmight be\nR",
+                        &transaction.sweater_config.supported_relations_kinds,
+                        &mut aliases_resolver,
+                    )
+                    .collect::<Vec<_>>()?[..],
+                    [Command::AddThesis(Thesis {
+                        content: Content::Text(_),
+                        ..
+                    })]
+                ));
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_forward_references() {
+        let mut sweater = new_default_sweater("test_forward_references");
+        sweater
+            .lock_all_and_write(|transaction| {
+                let commands = CommandsIterator::new(
+                    "+\nR\nmay be\nR-r\n\n+ (R-r).0\n[R-r] относительно истинно\n\n+ \
+                     R-r\nПолный релятивизм\n\n+ R\nРелятивизм",
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut AliasesResolver {
+                        read_able_transaction: transaction,
+                        known_aliases: BTreeMap::new(),
+                    },
+                )
+                .collect_with_forward_references()?;
+                assert_eq!(commands.len(), 4);
+                for command in commands {
+                    transaction.execute_command(&command)?;
+                }
+                assert!(transaction
+                    .get_thesis_id_by_alias(&Alias("(R-r).0".to_string()))?
+                    .is_some());
+
+                let mut aliases_resolver = AliasesResolver {
+                    read_able_transaction: transaction,
+                    known_aliases: BTreeMap::new(),
+                };
+                let example = std::fs::read_to_string("src/example.txt")?;
+                assert_eq!(
+                    CommandsIterator::new(
+                        &example,
+                        &transaction.sweater_config.supported_relations_kinds,
+                        &mut aliases_resolver,
+                    )
+                    .collect_with_forward_references()?,
+                    CommandsIterator::new(
+                        &example,
+                        &transaction.sweater_config.supported_relations_kinds,
+                        &mut aliases_resolver,
+                    )
+                    .collect::<Vec<_>>()?
+                );
+
+                let parse = |input: &str| {
+                    CommandsIterator::new(
+                        input,
+                        &transaction.sweater_config.supported_relations_kinds,
+                        &mut AliasesResolver {
+                            read_able_transaction: transaction,
+                            known_aliases: BTreeMap::new(),
+                        },
+                    )
+                    .collect_with_forward_references()
+                };
+                let thesis_alias = |command: &Command| match command {
+                    Command::AddThesis(thesis) => thesis.alias.clone().unwrap().0,
+                    command => panic!("Unexpected command {command:?}"),
+                };
+                assert_eq!(
+                    parse("+ C\nтретий [B]\n\n+ A\nпервый\n\n+ B\nвторой")?
+                        .iter()
+                        .map(thesis_alias)
+                        .collect::<Vec<_>>(),
+                    vec!["A", "B", "C"]
+                );
+                let commands = parse("+ A\nпервый\n\n+ B\nвторой\n\n@ A\nB\n\n+ C\nтретий [A]")?;
+                match (&commands[1], &commands[3]) {
+                    (Command::AddThesis(second), Command::AddThesis(third)) => {
+                        assert_eq!(third.references(), vec![transaction.thesis_id(second)?])
+                    }
+                    commands => panic!("Unexpected commands {commands:?}"),
+                }
+
+                let commands = parse(
+                    "namespace (P)\n\n+\n.a\nmay be\n.d\n.e\n\n+ .a\nначало\n\n\
+                     + .d\nвывод\n\n+ .e\nследствие\n\nnamespace",
+                )?;
+                assert_eq!(
+                    commands[..3].iter().map(thesis_alias).collect::<Vec<_>>(),
+                    vec!["(P).a", "(P).d", "(P).e"]
+                );
+                let ids = commands[..3]
+                    .iter()
+                    .map(|command| match command {
+                        Command::AddThesis(thesis) => transaction.thesis_id(thesis),
+                        command => panic!("Unexpected command {command:?}"),
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                assert_eq!(
+                    commands[3..]
+                        .iter()
+                        .map(|command| match command {
+                            Command::AddThesis(Thesis {
+                                content: Content::Relation(relation),
+                                ..
+                            }) => (relation.from.clone(), relation.to.clone()),
+                            command => panic!("Unexpected command {command:?}"),
+                        })
+                        .collect::<Vec<_>>(),
+                    vec![
+                        (ids[0].clone(), ids[1].clone()),
+                        (ids[0].clone(), ids[2].clone())
+                    ]
+                );
+
+                for (invalid_input, expected_error) in [
+                    ("+ A\nпервый\n\n+\nA\nmeans\nB", "Can not find declarations"),
+                    ("+ A\nпервый\n\n+ A\nвторой", "Duplicate alias"),
+                    ("+ A\nпервый [B]\n\n+ B\nвторой [A]", "depend on each other"),
+                ] {
+                    let error = parse(invalid_input).unwrap_err().to_string();
+                    assert!(error.contains(expected_error), "{error}");
+                }
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_text_charset() {
+        let mut sweater = new_default_sweater("test_text_charset");
+        let input = "+ Q\nЧто есть истина?\n\n+ E\nΠάντα ῥεῖ (всё течёт)";
+        sweater
+            .lock_all_and_write(|transaction| {
+                assert!(CommandsIterator::new(
+                    input,
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut AliasesResolver {
+                        read_able_transaction: transaction,
+                        known_aliases: BTreeMap::new(),
+                    },
+                )
+                .collect::<Vec<_>>()
+                .is_err());
+                Ok(())
+            })
+            .unwrap();
+
+        sweater.config.text_charset = TextCharset::new(
+            vec![
+                "Cyrillic".to_string(),
+                "Latin".to_string(),
+                "Greek".to_string(),
+            ],
+            ",-:.'\"?!()".to_string(),
+        );
+        sweater
+            .lock_all_and_write(|transaction| {
+                let commands = CommandsIterator::new(
+                    input,
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut AliasesResolver {
+                        read_able_transaction: transaction,
+                        known_aliases: BTreeMap::new(),
+                    },
+                )
+                .collect::<Vec<_>>()?;
+                assert_eq!(commands.len(), 2);
+                Ok(())
+            })
+            .unwrap();
+    }
 
+    #[test]
+    fn test_escaped_brackets() {
+        let mut sweater = new_default_sweater("test_escaped_brackets");
+        sweater
+            .lock_all_and_write(|transaction| {
+                let mut aliases_resolver = AliasesResolver {
+                    read_able_transaction: transaction,
+                    known_aliases: BTreeMap::new(),
+                };
+                let input = "\\[примечание\\] текст";
+                let text = Text::new(input, &mut aliases_resolver)?;
+                assert_eq!(text.references.len(), 0);
+                assert_eq!(text.raw_text_parts[0].0, "[примечание] текст");
+                assert_eq!(text.composed(), input);
+                assert!(Text::new("[неизвестно] текст", &mut aliases_resolver).is_err());
+                match &CommandsIterator::new(
+                    "+\nкосая \\\\ черта",
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut aliases_resolver,
+                )
+                .collect::<Vec<_>>()?[..]
+                {
+                    [Command::AddThesis(Thesis {
+                        content: Content::Text(text),
+                        ..
+                    })] => assert_eq!(text.raw_text_parts[0].0, "косая \\ черта"),
+                    commands => panic!("Unexpected commands {commands:?}"),
+                }
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_reference_labels() {
+        let mut sweater = new_default_sweater("test_reference_labels");
+        sweater
+            .lock_all_and_write(|transaction| {
+                let commands = CommandsIterator::new(
+                    "+ R-r\nПолный релятивизм\n\n+\nЭто [R-r|полный релятивизм] и [R-r]",
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut AliasesResolver {
+                        read_able_transaction: transaction,
+                        known_aliases: BTreeMap::new(),
+                    },
+                )
+                .collect::<Vec<_>>()?;
+                for command in commands.iter() {
+                    transaction.execute_command(command)?;
+                }
+                let labelled = match commands[1] {
+                    Command::AddThesis(ref thesis) => thesis.clone(),
+                    _ => panic!(),
+                };
+                let text = match labelled.content {
+                    Content::Text(ref text) => text.clone(),
+                    _ => panic!(),
+                };
+                assert_eq!(
+                    text.labels,
+                    vec![Some(RawText("полный релятивизм".to_string())), None]
+                );
+                assert_eq!(
+                    text.composed_with_aliases(&*transaction)?,
+                    "Это [R-r|полный релятивизм] и [R-r]"
+                );
+                let unlabelled = Text {
+                    labels: Vec::new(),
+                    ..text.clone()
+                };
+                assert_ne!(
+                    transaction.content_id(&Content::Text(unlabelled))?,
+                    transaction.content_id(&Content::Text(text.clone()))?
+                );
+                assert_eq!(
+                    transaction
+                        .get_thesis(&transaction.thesis_id(&labelled)?)?
+                        .unwrap()
+                        .content,
+                    Content::Text(text)
+                );
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_canonicalization() {
+        let mut sweater = new_default_sweater("test_canonicalization");
+        sweater
+            .lock_all_and_write(|transaction| {
+                let mut aliases_resolver = AliasesResolver {
+                    read_able_transaction: transaction,
+                    known_aliases: BTreeMap::new(),
+                };
+                let decomposed = "Полный  релятивизм и\u{0306}\n";
+                let composed = "Полный релятивизм й";
+                assert_eq!(
+                    Text::new(decomposed, &mut aliases_resolver)?,
+                    Text::new(composed, &mut aliases_resolver)?
+                );
+                Ok(())
+            })
+            .unwrap();
+
+        sweater
+            .lock_all_and_write(|transaction| {
+                let raw_text = |text: &str| Text {
+                    raw_text_parts: vec![RawText(text.to_string())],
+                    references: Vec::new(),
+                    start_with_reference: false,
+                    labels: Vec::new(),
+                };
+                let first = Thesis {
+                    alias: Some(Alias("R-r".to_string())),
+                    content: Content::Text(raw_text("Полный  релятивизм ")),
+                    tags: vec![Tag("first".to_string())],
+                };
+                let second = Thesis {
+                    alias: None,
+                    content: Content::Text(raw_text("Полный релятивизм")),
+                    tags: vec![Tag("second".to_string())],
+                };
+                let referencing = Thesis {
+                    alias: None,
+                    content: Content::Text(Text {
+                        raw_text_parts: vec![RawText(" верен  ".to_string())],
+                        references: vec![transaction.thesis_id(&first)?],
+                        start_with_reference: true,
+                        labels: Vec::new(),
+                    }),
+                    tags: Vec::new(),
+                };
+                for thesis in [&first, &second, &referencing] {
+                    transaction.insert_thesis(thesis.clone())?;
+                }
+
+                let new_ids = migration::canonicalize(transaction)?;
+                assert_eq!(
+                    new_ids[&transaction.thesis_id(&first)?],
+                    transaction.thesis_id(&second)?
+                );
+                let merged = transaction
+                    .get_thesis(&transaction.thesis_id(&second)?)?
+                    .unwrap();
+                assert_eq!(merged.alias, Some(Alias("R-r".to_string())));
+                assert_eq!(
+                    merged.tags,
+                    vec![Tag("first".to_string()), Tag("second".to_string())]
+                );
+                assert!(transaction
+                    .get_thesis(&transaction.thesis_id(&first)?)?
+                    .is_none());
+                assert!(transaction
+                    .get_thesis(&transaction.thesis_id(&referencing)?)?
+                    .is_none());
+                let rewired = transaction
+                    .get_thesis(&new_ids[&transaction.thesis_id(&referencing)?])?
+                    .unwrap();
+                assert_eq!(rewired.references(), vec![transaction.thesis_id(&second)?]);
+                match rewired.content {
+                    Content::Text(text) => {
+                        assert_eq!(
+                            text.composed(),
+                            format!("[{}] верен", transaction.thesis_id(&second)?)
+                        )
+                    }
+                    _ => panic!(),
+                }
+                assert!(migration::canonicalize(transaction)?.is_empty());
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_id_scheme_migration() {
+        let mut sweater = new_default_sweater("test_id_scheme_migration");
+        let new_id_scheme = IdScheme {
+            version: IdSchemeVersion::V2,
+            hash: IdHash::Blake3,
+        };
+        let old_relation_id = sweater
+            .lock_all_and_write(|transaction| {
+                let commands = CommandsIterator::new(
+                    "+ R\nРелятивизм\n\n+ R-r\nПолный [R]\n\n+ R_R-r\nR\nincludes\nR-r",
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut AliasesResolver {
+                        read_able_transaction: transaction,
+                        known_aliases: BTreeMap::new(),
+                    },
+                )
+                .collect::<Vec<_>>()?;
+                for command in commands.iter() {
+                    transaction.execute_command(command)?;
+                }
+                Ok(transaction
+                    .get_thesis_id_by_alias(&Alias("R_R-r".to_string()))?
+                    .unwrap())
+            })
+            .unwrap();
+
+        assert_eq!(sweater.change_id_scheme(new_id_scheme).unwrap().len(), 3);
+        assert_eq!(sweater.config.id_scheme, new_id_scheme);
+        sweater
+            .lock_all_writes_and_read(|transaction| {
+                assert!(transaction.get_thesis(&old_relation_id)?.is_none());
+                let mut theses = transaction.iter_theses()?.collect::<Vec<_>>()?;
+                assert_eq!(theses.len(), 3);
+                for thesis in theses.iter() {
+                    let thesis_id = transaction.thesis_id(thesis)?;
+                    assert_ne!(thesis_id, thesis.id_with(&IdScheme::default())?);
+                    assert_eq!(transaction.get_thesis(&thesis_id)?.as_ref(), Some(thesis));
+                    for referenced_thesis_id in thesis.references() {
+                        assert!(transaction.get_thesis(&referenced_thesis_id)?.is_some());
+                    }
+                }
+                theses.retain(|thesis| matches!(thesis.content, Content::Relation(_)));
+                assert_eq!(
+                    transaction
+                        .get_thesis_id_by_alias(&Alias("R_R-r".to_string()))?
+                        .unwrap(),
+                    theses[0].id_with(&new_id_scheme)?
+                );
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_id_prefixes() {
+        let mut sweater = new_default_sweater("test_id_prefixes");
+        sweater
+            .lock_all_and_write(|transaction| {
+                let mut texts_by_prefix: BTreeMap<String, String> = BTreeMap::new();
+                let (first, second) = (0..)
+                    .find_map(|i| {
+                        let text = format!("тезис {i}");
+                        let prefix = transaction
+                            .content_id(&Content::Text(Text {
+                                raw_text_parts: vec![RawText(text.clone())],
+                                references: Vec::new(),
+                                start_with_reference: false,
+                                labels: Vec::new(),
+                            }))
+                            .unwrap()
+                            .to_string()[..4]
+                            .to_string();
+                        texts_by_prefix
+                            .insert(prefix, text.clone())
+                            .map(|previous_text| (previous_text, text))
+                    })
+                    .unwrap();
+                let commands = CommandsIterator::new(
+                    &format!("+ first\n{first}\n\n+ second\n{second}"),
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut AliasesResolver {
+                        read_able_transaction: transaction,
+                        known_aliases: BTreeMap::new(),
+                    },
+                )
+                .collect::<Vec<_>>()?;
+                for command in commands.iter() {
+                    transaction.execute_command(command)?;
+                }
+                let first_id = transaction
+                    .get_thesis_id_by_alias(&Alias("first".to_string()))?
+                    .unwrap();
+                let second_id = transaction
+                    .get_thesis_id_by_alias(&Alias("second".to_string()))?
+                    .unwrap();
+                let first_prefix = transaction.get_shortest_unambiguous_prefix(&first_id)?;
+                assert!(first_prefix.len() > 4);
+                assert!(!second_id.to_string().starts_with(&first_prefix));
+
+                let mut aliases_resolver = AliasesResolver {
+                    read_able_transaction: transaction,
+                    known_aliases: BTreeMap::new(),
+                };
+                let text = Text::new(&format!("про [~{first_prefix}]"), &mut aliases_resolver)?;
+                assert_eq!(text.references, vec![first_id.clone()]);
+                assert_eq!(
+                    aliases_resolver.get_thesis_id_by_reference(&Reference::new(&format!(
+                        "~{first_prefix}"
+                    ))?)?,
+                    first_id
+                );
+                assert!(aliases_resolver
+                    .get_thesis_id_by_reference(&Reference::new(&first_prefix)?)
+                    .is_err());
+                let theses_names = transaction.get_theses_names()?;
+                assert_eq!(theses_names[&first_id], "first");
+                assert_eq!(
+                    transaction.get_shortest_unambiguous_prefixes()?[&first_id],
+                    first_prefix
+                );
+                let ambiguity_error = aliases_resolver
+                    .get_thesis_id_by_reference(&Reference::new(&format!(
+                        "~{}",
+                        &first_prefix[..4]
+                    ))?)
+                    .unwrap_err()
+                    .to_string();
+                assert!(
+                    ambiguity_error.contains(&first_id.to_string())
+                        && ambiguity_error.contains(&second_id.to_string()),
+                    "{ambiguity_error}"
+                );
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_idempotent_apply() {
+        let mut sweater = new_default_sweater("test_idempotent_apply");
+        let apply = |sweater: &mut Sweater, input: &str, mode: ApplyMode| {
+            sweater.lock_all_and_write(|transaction| {
+                let commands = CommandsIterator::new(
+                    input,
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut AliasesResolver {
+                        read_able_transaction: transaction,
+                        known_aliases: BTreeMap::new(),
+                    },
+                )
+                .in_mode(mode)
+                .collect::<Vec<_>>()?;
+                for command in commands.iter() {
+                    transaction.execute_command_in_mode(command, mode)?;
+                }
+                Ok(())
+            })
+        };
+        let input = "+ R #draft\nРелятивизм\n\n+ R-r\nПолный релятивизм\n\n+\nR\nmay be\nR-r";
+        apply(&mut sweater, input, ApplyMode::Strict).unwrap();
+        assert!(apply(&mut sweater, input, ApplyMode::Strict).is_err());
+        apply(&mut sweater, input, ApplyMode::Idempotent).unwrap();
+        apply(
+            &mut sweater,
+            "+ relativism #total\nРелятивизм",
+            ApplyMode::Idempotent,
+        )
+        .unwrap();
+
+        sweater
+            .lock_all_writes_and_read(|transaction| {
+                assert_eq!(transaction.iter_theses()?.count()?, 3);
+                let thesis_id = transaction
+                    .get_thesis_id_by_alias(&Alias("relativism".to_string()))?
+                    .unwrap();
+                assert_eq!(
+                    transaction.get_thesis(&thesis_id)?.unwrap().tags,
+                    vec![Tag("draft".to_string()), Tag("total".to_string())]
+                );
+                Ok(())
+            })
+            .unwrap();
+
+        assert!(apply(
+            &mut sweater,
+            "+ relativism\nПолный релятивизм",
+            ApplyMode::Idempotent
+        )
+        .is_err());
+
+        apply(&mut sweater, "-\nR-r", ApplyMode::Idempotent).unwrap();
+        apply(&mut sweater, "-\nR-r", ApplyMode::Idempotent).unwrap();
+        assert!(apply(&mut sweater, "-\nR-r", ApplyMode::Strict).is_err());
+        let removed_thesis_id = sweater
+            .lock_all_writes_and_read(|transaction| {
+                assert_eq!(transaction.iter_theses()?.count()?, 1);
+                transaction.content_id(&Content::Text(Text {
+                    raw_text_parts: vec![RawText("Полный релятивизм".to_string())],
+                    references: Vec::new(),
+                    start_with_reference: false,
+                    labels: Vec::new(),
+                }))
+            })
+            .unwrap();
+        apply(
+            &mut sweater,
+            &format!("-\n{removed_thesis_id}"),
+            ApplyMode::Idempotent,
+        )
+        .unwrap();
+        assert!(apply(
+            &mut sweater,
+            &format!("-\n{removed_thesis_id}"),
+            ApplyMode::Strict
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_sync() {
+        let mut sweater = new_default_sweater("test_sync");
+        let plan = |sweater: &mut Sweater, input: &str| {
+            sweater.lock_all_and_write(|transaction| {
+                let commands = CommandsIterator::new(
+                    input,
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut AliasesResolver {
+                        read_able_transaction: transaction,
+                        known_aliases: BTreeMap::new(),
+                    },
+                )
+                .collect::<Vec<_>>()?;
+                sync::plan(transaction, &commands)
+            })
+        };
+        let apply = |sweater: &mut Sweater, plan: &Vec<Command>| {
+            sweater.lock_all_and_write(|transaction| sync::apply(transaction, plan))
+        };
+
+        let initial_plan = plan(
+            &mut sweater,
+            "+ R #draft\nРелятивизм\n\n+ A\nАбсолютизм\n\n+\nR\nmay be\nA\n\n+ O\nОбъективизм",
+        )
+        .unwrap();
+        assert_eq!(initial_plan.len(), 4);
+        apply(&mut sweater, &initial_plan).unwrap();
+
+        let desired = "+ relativism #total\nРелятивизм\n\n+ A\nАбсолютизм\n\n+ S\nСубъективизм";
+        let changes_plan = plan(&mut sweater, desired).unwrap();
+        let relativism_id = sweater
+            .lock_all_writes_and_read(|transaction| {
+                transaction.content_id(&Content::Text(Text {
+                    raw_text_parts: vec![RawText("Релятивизм".to_string())],
+                    references: Vec::new(),
+                    start_with_reference: false,
+                    labels: Vec::new(),
+                }))
+            })
+            .unwrap();
+        assert_eq!(
+            changes_plan
+                .iter()
+                .filter(|command| matches!(command, Command::RemoveThesis(_)))
+                .count(),
+            2
+        );
+        assert!(changes_plan.contains(&Command::RemoveTags(
+            relativism_id.clone(),
+            vec![Tag("draft".to_string())]
+        )));
+        assert!(changes_plan.contains(&Command::AddTags(
+            relativism_id.clone(),
+            vec![Tag("total".to_string())]
+        )));
+        assert!(changes_plan.contains(&Command::SetAlias(
+            relativism_id.clone(),
+            Alias("relativism".to_string())
+        )));
+        assert!(matches!(changes_plan.last(), Some(Command::AddThesis(_))));
+        assert_eq!(changes_plan.len(), 6);
+        apply(&mut sweater, &changes_plan).unwrap();
+
+        sweater
+            .lock_all_writes_and_read(|transaction| {
+                assert_eq!(transaction.iter_theses()?.count()?, 3);
+                assert_eq!(
+                    transaction.get_thesis(&relativism_id)?.unwrap(),
+                    Thesis {
+                        alias: Some(Alias("relativism".to_string())),
+                        content: Content::Text(Text {
+                            raw_text_parts: vec![RawText("Релятивизм".to_string())],
+                            references: Vec::new(),
+                            start_with_reference: false,
+                            labels: Vec::new(),
+                        }),
+                        tags: vec![Tag("total".to_string())],
+                    }
+                );
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(plan(&mut sweater, desired).unwrap(), vec![]);
+
+        // relativism is referenced, but not added by desired commands
+        let referencing_desired = "+ A\nАбсолютизм\n\n+\nrelativism\nmay be\nA";
+        let referencing_plan = plan(&mut sweater, referencing_desired).unwrap();
+        assert_eq!(
+            referencing_plan
+                .iter()
+                .filter(|command| matches!(command, Command::RemoveThesis(_)))
+                .count(),
+            1
+        );
+        assert!(!referencing_plan.contains(&Command::RemoveThesis(relativism_id.clone())));
+        apply(&mut sweater, &referencing_plan).unwrap();
+        sweater
+            .lock_all_writes_and_read(|transaction| {
+                assert_eq!(transaction.iter_theses()?.count()?, 3);
+                assert!(transaction.get_thesis(&relativism_id)?.is_some());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(plan(&mut sweater, referencing_desired).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_streaming_commands() {
+        let mut sweater = new_default_sweater("test_streaming_commands");
+        sweater
+            .lock_all_and_write(|transaction| {
+                let input = std::fs::read_to_string("src/example.txt")?;
+                let parsed_from_string = CommandsIterator::new(
+                    &input,
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut AliasesResolver {
+                        read_able_transaction: transaction,
+                        known_aliases: BTreeMap::new(),
+                    },
+                )
+                .collect::<Vec<_>>()?;
+                let parsed_from_reader = CommandsIterator::from_reader(
+                    std::io::BufReader::new(std::fs::File::open("src/example.txt")?),
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut AliasesResolver {
+                        read_able_transaction: transaction,
+                        known_aliases: BTreeMap::new(),
+                    },
+                )
+                .collect::<Vec<_>>()?;
+                assert_eq!(parsed_from_reader, parsed_from_string);
+
+                let invalid_input = "// comment\r\n+ R\r\nРелятивизм\r\n\r\n!\r\nR\r\n";
+                let error_from_string = CommandsIterator::new(
+                    invalid_input,
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut AliasesResolver {
+                        read_able_transaction: transaction,
+                        known_aliases: BTreeMap::new(),
+                    },
+                )
+                .collect::<Vec<_>>()
+                .unwrap_err()
+                .to_string();
+                let error_from_reader = CommandsIterator::from_reader(
+                    invalid_input.as_bytes(),
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut AliasesResolver {
+                        read_able_transaction: transaction,
+                        known_aliases: BTreeMap::new(),
+                    },
+                )
+                .collect::<Vec<_>>()
+                .unwrap_err()
+                .to_string();
+                assert_eq!(error_from_reader, error_from_string);
+                assert!(
+                    error_from_reader.contains("2-th paragraph at line 5"),
+                    "{error_from_reader}"
+                );
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_formatter() {
+        let input = "  // theses about relativism  \n+   R   #draft  #core \n\
+                     \x20 Релятивизм   как  он   есть  \n\n\n\n+  R-r\n; comment inside paragraph\n\
+                     Полный [R]  релятивизм\n   \n\n#  \nR\n draft \n\ninclude   other.txt  \n";
+        let expected = "// theses about relativism\n+ R #draft #core\nРелятивизм как он есть\n\n\
+                        + R-r\n; comment inside paragraph\nПолный [R] релятивизм\n\n\
+                        #\nR\ndraft\n\ninclude other.txt\n";
+        assert_eq!(formatter::format(input, None).unwrap(), expected);
+        assert_eq!(formatter::format(expected, None).unwrap(), expected);
+        assert!(formatter::format("+ R\nРелятивизм\n\n! R\nR", None).is_err());
+        // include is directive only when it is the only line of paragraph, as when reading files
+        assert!(formatter::format("include other.txt\n+ R\nРелятивизм", None).is_err());
+        assert_eq!(
+            formatter::format("// first\n \t \n// second\n+ R\n; inner\n Релятивизм", None)
+                .unwrap(),
+            "// first\n\n// second\n+ R\n; inner\nРелятивизм\n"
+        );
+        assert_eq!(
+            formatter::format("+\nR\n may  be \nR-r", None).unwrap(),
+            "+\nR\nmay  be\nR-r\n"
+        );
+
+        let mut sweater = new_default_sweater("test_formatter");
+        sweater
+            .lock_all_and_write(|transaction| {
+                let commands = CommandsIterator::new(
+                    "+ R\nРелятивизм\n\n+ R-r\nПолный релятивизм",
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut AliasesResolver {
+                        read_able_transaction: transaction,
+                        known_aliases: BTreeMap::new(),
+                    },
+                )
+                .collect::<Vec<_>>()?;
+                for command in commands {
+                    transaction.execute_command(&command)?;
+                }
+                let relativism_id = transaction
+                    .get_thesis_id_by_alias(&Alias("R".to_string()))?
+                    .unwrap();
+                let total_relativism_id = transaction
+                    .get_thesis_id_by_alias(&Alias("R-r".to_string()))?
+                    .unwrap();
+                assert_eq!(
+                    formatter::format(
+                        &format!(
+                            "+\n{relativism_id}\nmay be\n{total_relativism_id}\n\n\
+                             +\nпро [{relativism_id}|него] и \\[{relativism_id}\\]\n\n\
+                             ^\n{relativism_id}\ndraft"
+                        ),
+                        Some(transaction)
+                    )?,
+                    format!(
+                        "+\nR\nmay be\nR-r\n\n+\nпро [R|него] и \\[{relativism_id}\\]\n\n\
+                         ^\nR\ndraft\n"
+                    )
+                );
+                assert_eq!(
+                    formatter::format(
+                        "+\nпервая  строка\nmeans\nвторая  строка\nтретья",
+                        Some(transaction)
+                    )?,
+                    "+\nпервая  строка\nmeans\nвторая  строка\nтретья\n"
+                );
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_analysis() {
+        let mut sweater = new_default_sweater("test_analysis");
+        sweater
+            .lock_all_and_write(|transaction| {
+                let commands = CommandsIterator::new(
+                    "+ R #draft\nРелятивизм",
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut AliasesResolver {
+                        read_able_transaction: transaction,
+                        known_aliases: BTreeMap::new(),
+                    },
+                )
+                .collect::<Vec<_>>()?;
+                for command in commands {
+                    transaction.execute_command(&command)?;
+                }
+                Ok(())
+            })
+            .unwrap();
+        let document =
+            "+ R-r #core\nПолный [R]\n\n+\nR-r\nmay be\nR\n\n-\nunknown\n\n#\nR\nnew\n\n\
+                        +\nО [R-r|нём]";
+        let position = |line: usize, character: usize| Position { line, character };
+        sweater
+            .lock_all_writes_and_read(|transaction| {
+                let analysis = Analysis::new(document, &transaction)?;
+
+                assert_eq!(analysis.diagnostics().len(), 1);
+                assert_eq!(analysis.diagnostics()[0].range.start, position(8, 0));
+                assert_eq!(analysis.diagnostics()[0].range.end, position(9, 7));
+
+                assert_eq!(
+                    analysis.definition(position(4, 1)).unwrap().start,
+                    position(0, 2)
+                );
+                assert_eq!(
+                    analysis.definition(position(16, 5)).unwrap().start,
+                    position(0, 2)
+                );
+                assert_eq!(analysis.definition(position(1, 14)), None);
+
+                assert_eq!(
+                    analysis.hover(position(1, 14))?,
+                    Some("Релятивизм\n\n#draft".to_string())
+                );
+                assert_eq!(
+                    analysis.hover(position(4, 1))?,
+                    Some("Полный [R]\n\n#core".to_string())
+                );
+                assert_eq!(analysis.hover(position(5, 1))?, None);
+
+                let labels = |completions: Vec<Completion>| {
+                    completions
+                        .into_iter()
+                        .map(|completion| completion.label)
+                        .collect::<Vec<_>>()
+                };
+                assert_eq!(labels(analysis.completions(position(12, 0))?), ["R", "R-r"]);
+                assert_eq!(
+                    labels(analysis.completions(position(5, 0))?),
+                    transaction
+                        .sweater_config
+                        .supported_relations_kinds
+                        .iter()
+                        .map(|relation_kind| relation_kind.0.clone())
+                        .collect::<Vec<_>>()
+                );
+                assert_eq!(
+                    labels(analysis.completions(position(0, 11))?),
+                    ["core", "draft"]
+                );
+
+                assert_eq!(
+                    analysis
+                        .rename(position(4, 1), "total")?
+                        .into_iter()
+                        .map(|edit| (edit.range.start, edit.range.end))
+                        .collect::<Vec<_>>(),
+                    [
+                        (position(0, 2), position(0, 5)),
+                        (position(4, 0), position(4, 3)),
+                        (position(16, 4), position(16, 7))
+                    ]
+                );
+                assert!(analysis.rename(position(4, 1), "R").is_err());
+                assert!(analysis.rename(position(5, 1), "total").is_err());
+                assert!(analysis.rename(position(6, 0), "relativism").is_err());
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_batch_relations() {
+        let mut sweater = new_default_sweater("test_batch_relations");
+        sweater
+            .lock_all_and_write(|transaction| {
+                let theses = "+ R\nРелятивизм\n\n+ R-r\nПолный релятивизм\n\n\
+                              + R-a\nАбсолютный релятивизм\n\n+ R-m\nМорской релятивизм";
+                let parse = |input: &str| {
+                    CommandsIterator::new(
+                        &format!("{theses}\n\n{input}"),
+                        &transaction.sweater_config.supported_relations_kinds,
+                        &mut AliasesResolver {
+                            read_able_transaction: transaction,
+                            known_aliases: BTreeMap::new(),
+                        },
+                    )
+                    .collect::<Vec<_>>()
+                };
+
+                let commands = parse("+ #batch\nR\nmay be\nR-r\nR-a\nmeans\nR-m")?;
+                assert_eq!(commands.len(), 7);
+                let relations = commands[4..]
+                    .iter()
+                    .map(|command| match command {
+                        Command::AddThesis(Thesis {
+                            alias: None,
+                            content: Content::Relation(relation),
+                            tags,
+                        }) if *tags == vec![Tag("batch".to_string())] => {
+                            (relation.kind.0.as_str(), relation.to.clone())
+                        }
+                        command => panic!("Unexpected command {command:?}"),
+                    })
+                    .collect::<Vec<_>>();
+                let thesis_id = |command: &Command| match command {
+                    Command::AddThesis(thesis) => transaction.thesis_id(thesis).unwrap(),
+                    command => panic!("Unexpected command {command:?}"),
+                };
+                assert_eq!(
+                    relations,
+                    vec![
+                        ("may be", thesis_id(&commands[1])),
+                        ("may be", thesis_id(&commands[2])),
+                        ("means", thesis_id(&commands[3])),
+                    ]
+                );
+                assert_eq!(
+                    parse("+\nR\nmay be\nR-r\nR-a\nmeans\nR-m")?,
+                    parse(
+                        "+\nR\nmay be\nR-r\n\n+\nR\nmay be\nR-a\n\n\
+                         +\nR\nmeans\nR-m"
+                    )?
+                );
+                assert_eq!(parse("+ alias\nR\nmay be\nR-r")?.len(), 5);
+
+                let error = parse("+\nR\nmay be\nR-r\nR-x").unwrap_err().to_string();
+                assert!(error.contains("on line 17"), "{error}");
+                let error = parse("+\nR\nmay be\nmeans\nR-m").unwrap_err().to_string();
+                assert!(error.contains("on line 15"), "{error}");
+                assert!(error.contains("there is no thesis"), "{error}");
+                assert!(parse("+\nR\nmay be\nR-r\nmeans").is_err());
+                assert!(parse("+ alias\nR\nmay be\nR-r\nR-a").is_err());
+
+                let error = parse("+\nR\nmay be\nR-r\nR-x\nmeans\nR-y")
+                    .unwrap_err()
+                    .to_string();
+                assert!(
+                    error.contains("on line 17") && error.contains("on line 19"),
+                    "{error}"
+                );
+                let error = parse("+\nRR\nmay be\nR-r\nR-a").unwrap_err().to_string();
+                assert!(error.contains("on line 14"), "{error}");
+                let error = parse("+\nR\nmay be\nR-x\nR-a").unwrap_err().to_string();
+                assert!(error.contains("on line 16"), "{error}");
+                assert!(parse("+\nпервая строка\nmeans\nвторая строка\nтретья строка").is_err());
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_relations_kinds_shorthands() {
+        let config = |test_name_for_isolation: &str, shorthands: &[(&str, &str)]| {
+            let mut result: SweaterConfig = serde_saphyr::from_str(
+                &std::fs::read_to_string("src/test_sweater_config.yml")
+                    .unwrap()
+                    .replace("TEST_NAME", test_name_for_isolation),
+            )
+            .unwrap();
+            for (relation_kind, shorthand) in shorthands {
+                result
+                    .relations_kinds_shorthands
+                    .entry(RelationKind(relation_kind.to_string()))
+                    .or_default()
+                    .push(shorthand.to_string());
+            }
+            result
+        };
+        assert!(Sweater::new(config(
+            "test_relations_kinds_shorthands",
+            &[("follows", "->")]
+        ))
+        .is_err());
+        assert!(Sweater::new(config(
+            "test_relations_kinds_shorthands",
+            &[("therefore", "=>"), ("means", "=>")]
+        ))
+        .is_err());
+        assert!(Sweater::new(config(
+            "test_relations_kinds_shorthands",
+            &[("therefore", "means")]
+        ))
+        .is_err());
+
+        let mut sweater_config = config(
+            "test_relations_kinds_shorthands",
+            &[
+                ("therefore", "=>"),
+                ("therefore", "следовательно"),
+                ("negates", "!"),
+            ],
+        );
+        sweater_config.exported_relation_kind_form = RelationKindForm::Shorthand;
+        let mut sweater = Sweater::new(sweater_config).unwrap();
+        let directory =
+            std::path::Path::new("/tmp/woollib/test/test_relations_kinds_shorthands/vault");
+        let _ = std::fs::remove_dir_all(directory);
+        sweater
+            .lock_all_and_write(|transaction| {
+                let commands = CommandsIterator::new(
+                    "+ R\nРелятивизм\n\n+ R-r\nПолный релятивизм\n\n+ A\nАбсолютизм\n\n\
+                     +\nR\n=>\nR-r\n\n+\nR-r\nследовательно\nR\n!\nA",
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut AliasesResolver {
+                        read_able_transaction: transaction,
+                        known_aliases: BTreeMap::new(),
+                    },
+                )
+                .collect::<Vec<_>>()?;
+                let relations_kinds = commands
+                    .iter()
+                    .filter_map(|command| match command {
+                        Command::AddThesis(Thesis {
+                            content: Content::Relation(relation),
+                            ..
+                        }) => Some(relation.kind.0.as_str()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(relations_kinds, ["therefore", "therefore", "negates"]);
+                for command in commands.iter() {
+                    transaction.execute_command(command)?;
+                }
+                VaultExporter::new(transaction)?.write(directory)?;
+                let graph = GraphExchangeGenerator::new(
+                    &GraphExchangeGeneratorConfig {
+                        format: GraphExchangeFormat::GraphML,
+                    },
+                    transaction,
+                )?
+                .collect::<Vec<_>>()?
+                .join("");
+                assert_eq!(graph.matches("<data key=\"kind\">=&gt;</data>").count(), 2);
+                assert!(!graph.contains("therefore"));
+                Ok(())
+            })
+            .unwrap();
+
+        let notes = std::fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            notes
+                .iter()
+                .filter(|note| note.contains("kind: \"=>\""))
+                .count(),
+            2
+        );
+        assert_eq!(
+            notes
+                .iter()
+                .filter(|note| note.contains("kind: \"!\""))
+                .count(),
+            1
+        );
+
+        let mut imported_sweater = Sweater::new(config(
+            "test_relations_kinds_shorthands_imported",
+            &[("therefore", "=>"), ("negates", "!")],
+        ))
+        .unwrap();
+        let imported = imported_sweater
+            .lock_all_and_write(|transaction| {
+                let commands = VaultImporter::new(&mut AliasesResolver {
+                    read_able_transaction: transaction,
+                    known_aliases: BTreeMap::new(),
+                })
+                .commands(directory)?;
+                for command in commands.iter() {
+                    transaction.execute_command(command)?;
+                }
+                transaction.iter_theses()?.collect::<Vec<_>>()
+            })
+            .unwrap();
+        let exported = sweater
+            .lock_all_writes_and_read(|transaction| transaction.iter_theses()?.collect::<Vec<_>>())
+            .unwrap();
+        assert_eq!(imported, exported);
+    }
+
+    #[test]
+    fn test_namespaces() {
+        let document = "+ R\nРелятивизм\n\nnamespace (R)\n\n+ .r\nПолный [R]\n\n+ .a\nПро [.r]\n\n\
+                        +\n.r\ntherefore\n.a\n\nnamespace\n\n+ A\nАбсолютизм";
+        let position = |line: usize, character: usize| Position { line, character };
+        let mut sweater = new_default_sweater("test_namespaces");
+        sweater
+            .lock_all_writes_and_read(|transaction| {
+                let analysis = Analysis::new(document, &transaction)?;
+                assert!(analysis.diagnostics().is_empty());
+                assert_eq!(
+                    analysis.definition(position(12, 1)).unwrap().start,
+                    position(5, 2)
+                );
+                assert_eq!(
+                    analysis
+                        .rename(position(9, 8), "(R).total")?
+                        .into_iter()
+                        .map(|edit| (edit.range.start, edit.range.end, edit.new_text))
+                        .collect::<Vec<_>>(),
+                    [
+                        (position(5, 2), position(5, 4), ".total".to_string()),
+                        (position(9, 8), position(9, 10), ".total".to_string()),
+                        (position(12, 0), position(12, 2), ".total".to_string()),
+                    ]
+                );
+                Ok(())
+            })
+            .unwrap();
+        sweater
+            .lock_all_and_write(|transaction| {
+                let commands = CommandsIterator::new(
+                    document,
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut AliasesResolver {
+                        read_able_transaction: transaction,
+                        known_aliases: BTreeMap::new(),
+                    },
+                )
+                .collect::<Vec<_>>()?;
+                let aliases = commands
+                    .iter()
+                    .map(|command| match command {
+                        Command::AddThesis(thesis) => {
+                            thesis.alias.as_ref().map(|alias| alias.0.as_str())
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    aliases,
+                    [Some("R"), Some("(R).r"), Some("(R).a"), None, Some("A")]
+                );
+                for command in commands.iter() {
+                    transaction.execute_command(command)?;
+                }
+                let relative_id = transaction
+                    .get_thesis_id_by_alias(&Alias("(R).r".to_string()))?
+                    .unwrap();
+                let absolute_id = transaction
+                    .get_thesis_id_by_alias(&Alias("(R).a".to_string()))?
+                    .unwrap();
+                assert_eq!(
+                    transaction.get_aliases_by_prefix("(R)")?,
+                    [
+                        (Alias("(R).a".to_string()), absolute_id.clone()),
+                        (Alias("(R).r".to_string()), relative_id.clone())
+                    ]
+                );
+
+                assert!(transaction.get_aliases_by_prefix("(R).")?.is_empty());
+                assert!(transaction.get_aliases_by_prefix("(")?.is_empty());
+
+                transaction.rename_aliases_subtree("(R)", "(Q)")?;
+                assert!(transaction.get_aliases_by_prefix("(R)")?.is_empty());
+                assert_eq!(
+                    transaction.get_aliases_by_prefix("(Q)")?,
+                    [
+                        (Alias("(Q).a".to_string()), absolute_id),
+                        (Alias("(Q).r".to_string()), relative_id)
+                    ]
+                );
+                assert!(transaction.rename_aliases_subtree("(Q).a", "A").is_err());
+                assert!(transaction.rename_aliases_subtree("(Q)", "[Q]").is_err());
+                Ok(())
+            })
+            .unwrap();
+
+        let expected = "namespace (R)\n\n+ .r\nПолный\n\nnamespace\n";
+        assert_eq!(
+            formatter::format(
+                "namespace   (R)\n\n+   .r\n  Полный\n\n\nnamespace  \n",
+                None
+            )
+            .unwrap(),
+            expected
+        );
+        assert_eq!(formatter::format(expected, None).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_lints() {
+        let mut sweater = new_default_sweater("test_lints");
+        sweater
+            .lock_all_and_write(|transaction| {
+                let commands = CommandsIterator::new(
+                    "+ R #core\nРелятивизм\n\n+ R-r #core\nПолный релятивизм\n\n\
+                     + r-r #rare\nполный,  релятивизм.\n\n+ S\nСам\n\n\
+                     +\nR\ntherefore\nR-r\n\n+\nR\nnegates\nR-r\n\n+\nS\nmeans\nS",
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut AliasesResolver {
+                        read_able_transaction: transaction,
+                        known_aliases: BTreeMap::new(),
+                    },
+                )
+                .collect::<Vec<_>>()?;
+                for command in commands {
+                    transaction.execute_command(&command)?;
+                }
+                Ok(())
+            })
+            .unwrap();
+        sweater
+            .lock_all_writes_and_read(|transaction| {
+                let thesis_id = |alias: &str| {
+                    transaction
+                        .get_thesis_id_by_alias(&Alias(alias.to_string()))
+                        .unwrap()
+                        .unwrap()
+                };
+                let kinds =
+                    |lints: &[lints::Lint]| lints.iter().map(|lint| lint.kind).collect::<Vec<_>>();
+
+                let all = lints::lint(&LintsConfig::default(), &transaction, None)?;
+                assert_eq!(
+                    kinds(&all),
+                    [
+                        LintKind::UnusedAlias,
+                        LintKind::UnrelatedText,
+                        LintKind::SimilarTexts,
+                        LintKind::SelfRelation,
+                        LintKind::ConflictingRelations,
+                        LintKind::SingleUseTag
+                    ]
+                );
+                assert_eq!(all[0].theses, [thesis_id("r-r")]);
+                assert_eq!(all[1].theses, [thesis_id("r-r")]);
+                let mut similar = vec![thesis_id("R-r"), thesis_id("r-r")];
+                similar.sort();
+                assert_eq!(all[2].theses, similar);
+                assert_eq!(all[4].theses.len(), 2);
+                assert_eq!(all[5].theses, [thesis_id("r-r")]);
+                assert_eq!(
+                    serde_json::to_value(&all[5])?["kind"],
+                    serde_json::json!("single_use_tag")
+                );
+
+                let config: LintsConfig =
+                    serde_saphyr::from_str("allowed:\n  - unrelated_text\n  - single_use_tag")?;
+                assert_eq!(
+                    kinds(&lints::lint(&config, &transaction, None)?),
+                    [
+                        LintKind::UnusedAlias,
+                        LintKind::SimilarTexts,
+                        LintKind::SelfRelation,
+                        LintKind::ConflictingRelations
+                    ]
+                );
+
+                let commands = CommandsIterator::new(
+                    "+ T #core\nТеза",
+                    &transaction.sweater_config.supported_relations_kinds,
+                    &mut AliasesResolver {
+                        read_able_transaction: &transaction,
+                        known_aliases: BTreeMap::new(),
+                    },
+                )
+                .collect::<Vec<_>>()?;
+                let added = lints::lint(&LintsConfig::default(), &transaction, Some(&commands))?;
+                assert_eq!(
+                    kinds(&added),
+                    [LintKind::UnusedAlias, LintKind::UnrelatedText]
+                );
+                assert!(added.iter().all(|lint| lint.theses
+                    == [transaction
+                        .thesis_id(match &commands[0] {
+                            Command::AddThesis(thesis) => thesis,
+                            _ => unreachable!(),
+                        })
+                        .unwrap()]));
+
+                let removals = transaction
+                    .iter_theses()?
+                    .filter_map(|thesis| {
+                        Ok(match thesis.content {
+                            Content::Relation(ref relation) if relation.from == thesis_id("R") => {
+                                Some(Command::RemoveThesis(transaction.thesis_id(&thesis)?))
+                            }
+                            _ => None,
+                        })
+                    })
+                    .collect::<Vec<_>>()?;
+                assert_eq!(removals.len(), 2);
+                let unrelated =
+                    lints::lint(&LintsConfig::default(), &transaction, Some(&removals))?;
+                assert_eq!(
+                    kinds(&unrelated),
+                    [
+                        LintKind::UnusedAlias,
+                        LintKind::UnusedAlias,
+                        LintKind::UnrelatedText,
+                        LintKind::UnrelatedText,
+                        LintKind::SimilarTexts
+                    ]
+                );
                 Ok(())
             })
             .unwrap();
@@ -0,0 +1,351 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+use fallible_iterator::FallibleIterator;
+use serde::{Deserialize, Serialize};
+use trove::ObjectId;
+
+use crate::commands::Command;
+use crate::content::Content;
+use crate::read_transaction::{self, ReadTransactionMethods};
+use crate::relation::Relation;
+use crate::sync::DesiredState;
+use crate::tag::Tag;
+use crate::text::{Text, TextPart};
+use crate::thesis::Thesis;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum LintKind {
+    UnusedAlias,
+    UnrelatedText,
+    SimilarTexts,
+    SelfRelation,
+    ConflictingRelations,
+    SingleUseTag,
+}
+
+// lints listed in allowed are not checked
+#[derive(Serialize, Deserialize, Default)]
+pub struct LintsConfig {
+    #[serde(default)]
+    pub allowed: BTreeSet<LintKind>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+    pub kind: LintKind,
+    pub theses: Vec<ObjectId>,
+    pub message: String,
+}
+
+struct Linter<'a> {
+    config: &'a LintsConfig,
+    theses: BTreeMap<ObjectId, Thesis>,
+    // computed once for all theses, including ones added by linted commands
+    names: BTreeMap<ObjectId, String>,
+    linted: Option<BTreeSet<ObjectId>>,
+    result: Vec<Lint>,
+}
+
+impl Linter<'_> {
+    // lint is reported only if it concerns at least one linted thesis
+    fn report(&mut self, kind: LintKind, theses: Vec<ObjectId>, message: String) {
+        if self
+            .linted
+            .as_ref()
+            .is_none_or(|linted| theses.iter().any(|thesis_id| linted.contains(thesis_id)))
+        {
+            self.result.push(Lint {
+                kind,
+                theses,
+                message,
+            });
+        }
+    }
+
+    fn name(&self, thesis_id: &ObjectId) -> String {
+        self.names
+            .get(thesis_id)
+            .cloned()
+            .unwrap_or_else(|| thesis_id.to_string())
+    }
+
+    fn relations(&self) -> impl Iterator<Item = (&ObjectId, &Relation)> {
+        self.theses
+            .iter()
+            .filter_map(|(thesis_id, thesis)| match thesis.content {
+                Content::Relation(ref relation) => Some((thesis_id, relation)),
+                _ => None,
+            })
+    }
+
+    fn texts(&self) -> impl Iterator<Item = (&ObjectId, &Text)> {
+        self.theses
+            .iter()
+            .filter_map(|(thesis_id, thesis)| match thesis.content {
+                Content::Text(ref text) => Some((thesis_id, text)),
+                _ => None,
+            })
+    }
+
+    fn unused_aliases(&mut self) {
+        let referenced = self
+            .theses
+            .values()
+            .flat_map(|thesis| thesis.references())
+            .collect::<BTreeSet<_>>();
+        let unused = self
+            .theses
+            .iter()
+            .filter_map(|(thesis_id, thesis)| {
+                thesis
+                    .alias
+                    .as_ref()
+                    .filter(|_| !referenced.contains(thesis_id))
+                    .map(|alias| (thesis_id.clone(), alias.0.clone()))
+            })
+            .collect::<Vec<_>>();
+        for (thesis_id, alias) in unused {
+            self.report(
+                LintKind::UnusedAlias,
+                vec![thesis_id],
+                format!("Alias {alias:?} is declared, but thesis is never referenced"),
+            );
+        }
+    }
+
+    fn unrelated_texts(&mut self) {
+        let related = self
+            .relations()
+            .flat_map(|(_, relation)| [relation.from.clone(), relation.to.clone()])
+            .collect::<BTreeSet<_>>();
+        let unrelated = self
+            .texts()
+            .map(|(thesis_id, _)| thesis_id)
+            .filter(|thesis_id| !related.contains(thesis_id))
+            .cloned()
+            .collect::<Vec<_>>();
+        for thesis_id in unrelated {
+            let message = format!(
+                "Text thesis {:?} is not related to any thesis",
+                self.name(&thesis_id)
+            );
+            self.report(LintKind::UnrelatedText, vec![thesis_id], message);
+        }
+    }
+
+    // texts are similar if they are the same when lowercased, with punctuation removed and
+    // whitespaces runs collapsed
+    fn similar_texts(&mut self) {
+        let mut groups: BTreeMap<String, Vec<ObjectId>> = BTreeMap::new();
+        for (thesis_id, text) in self.texts() {
+            let key = text
+                .parts()
+                .iter()
+                .map(|part| match part {
+                    TextPart::Raw(raw_text) => raw_text
+                        .0
+                        .chars()
+                        .filter(|character| {
+                            character.is_alphanumeric() || character.is_whitespace()
+                        })
+                        .flat_map(|character| character.to_lowercase())
+                        .collect::<String>(),
+                    TextPart::Reference(reference, _) => format!(" {reference} "),
+                })
+                .collect::<String>()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ");
+            groups.entry(key).or_default().push(thesis_id.clone());
+        }
+        for theses_ids in groups.into_values().filter(|group| group.len() > 1) {
+            let names = theses_ids
+                .iter()
+                .map(|thesis_id| self.name(thesis_id))
+                .collect::<Vec<_>>();
+            self.report(
+                LintKind::SimilarTexts,
+                theses_ids,
+                format!("Texts of theses {names:?} differ only in case or punctuation"),
+            );
+        }
+    }
+
+    fn self_relations(&mut self) {
+        let self_relations = self
+            .relations()
+            .filter(|(_, relation)| relation.from == relation.to)
+            .map(|(thesis_id, relation)| (thesis_id.clone(), relation.from.clone()))
+            .collect::<Vec<_>>();
+        for (thesis_id, related_id) in self_relations {
+            let message = format!(
+                "Relation {:?} relates thesis {:?} to itself",
+                self.name(&thesis_id),
+                self.name(&related_id)
+            );
+            self.report(LintKind::SelfRelation, vec![thesis_id], message);
+        }
+    }
+
+    fn conflicting_relations(&mut self) {
+        let mut groups: BTreeMap<(ObjectId, ObjectId), Vec<(ObjectId, String)>> = BTreeMap::new();
+        for (thesis_id, relation) in self.relations() {
+            groups
+                .entry((relation.from.clone(), relation.to.clone()))
+                .or_default()
+                .push((thesis_id.clone(), relation.kind.0.clone()));
+        }
+        for ((from_id, to_id), relations) in groups {
+            let kinds = relations
+                .iter()
+                .map(|(_, kind)| kind.as_str())
+                .collect::<BTreeSet<_>>();
+            if kinds.len() > 1 {
+                let message = format!(
+                    "Thesis {:?} is related to thesis {:?} with different kinds {kinds:?}",
+                    self.name(&from_id),
+                    self.name(&to_id)
+                );
+                self.report(
+                    LintKind::ConflictingRelations,
+                    relations
+                        .into_iter()
+                        .map(|(thesis_id, _)| thesis_id)
+                        .collect(),
+                    message,
+                );
+            }
+        }
+    }
+
+    fn single_use_tags(&mut self) {
+        let mut usages: BTreeMap<Tag, Vec<ObjectId>> = BTreeMap::new();
+        for (thesis_id, thesis) in self.theses.iter() {
+            for tag in thesis.tags.iter() {
+                usages
+                    .entry(tag.clone())
+                    .or_default()
+                    .push(thesis_id.clone());
+            }
+        }
+        for (tag, theses_ids) in usages.into_iter().filter(|(_, usages)| usages.len() == 1) {
+            let message = format!(
+                "Tag {:?} is used only by thesis {:?}",
+                tag.0,
+                self.name(&theses_ids[0])
+            );
+            self.report(LintKind::SingleUseTag, theses_ids, message);
+        }
+    }
+}
+
+// relations ids with ids of theses they relate
+fn relations_endpoints(
+    theses: &BTreeMap<ObjectId, Thesis>,
+) -> BTreeMap<ObjectId, (ObjectId, ObjectId)> {
+    theses
+        .iter()
+        .filter_map(|(thesis_id, thesis)| match thesis.content {
+            Content::Relation(ref relation) => Some((
+                thesis_id.clone(),
+                (relation.from.clone(), relation.to.clone()),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+// checks theses which are legal but probably wrong; if commands are given, they are applied to
+// sweater theses in memory and only lints concerning theses they add or change, or theses
+// related by relations they add or remove, are reported
+pub fn lint<'a>(
+    config: &LintsConfig,
+    read_able_transaction: &dyn ReadTransactionMethods<'a>,
+    commands: Option<&[Command]>,
+) -> Result<Vec<Lint>> {
+    let mut state = DesiredState::default();
+    let mut theses = read_able_transaction.iter_theses()?;
+    while let Some(thesis) = theses.next()? {
+        state
+            .theses
+            .insert(read_able_transaction.thesis_id(&thesis)?, thesis);
+    }
+    drop(theses);
+    let linted = match commands {
+        Some(commands) => {
+            let relations_before = relations_endpoints(&state.theses);
+            let mut linted = BTreeSet::new();
+            for command in commands {
+                state.apply(read_able_transaction, command)?;
+                linted.insert(match command {
+                    Command::AddThesis(thesis) => read_able_transaction.thesis_id(thesis)?,
+                    Command::RemoveThesis(thesis_id)
+                    | Command::AddTags(thesis_id, _)
+                    | Command::RemoveTags(thesis_id, _)
+                    | Command::SetAlias(thesis_id, _) => thesis_id.clone(),
+                });
+            }
+            let relations_after = relations_endpoints(&state.theses);
+            for (from, to) in relations_before
+                .iter()
+                .filter(|(thesis_id, _)| !relations_after.contains_key(thesis_id))
+                .chain(
+                    relations_after
+                        .iter()
+                        .filter(|(thesis_id, _)| !relations_before.contains_key(thesis_id)),
+                )
+                .map(|(_, endpoints)| endpoints)
+            {
+                linted.insert(from.clone());
+                linted.insert(to.clone());
+            }
+            Some(linted)
+        }
+        None => None,
+    };
+
+    let mut theses_ids = state
+        .theses
+        .keys()
+        .map(|thesis_id| (thesis_id.to_string(), thesis_id.clone()))
+        .collect::<Vec<_>>();
+    theses_ids.sort();
+    let mut names = read_transaction::shortest_unambiguous_prefixes(theses_ids);
+    for (thesis_id, name) in names.iter_mut() {
+        *name = match state.theses[thesis_id].alias {
+            Some(ref alias) => alias.0.clone(),
+            None => format!("~{name}"),
+        };
+    }
+
+    let mut linter = Linter {
+        config,
+        theses: state.theses,
+        names,
+        linted,
+        result: Vec::new(),
+    };
+    for kind in [
+        LintKind::UnusedAlias,
+        LintKind::UnrelatedText,
+        LintKind::SimilarTexts,
+        LintKind::SelfRelation,
+        LintKind::ConflictingRelations,
+        LintKind::SingleUseTag,
+    ] {
+        if linter.config.allowed.contains(&kind) {
+            continue;
+        }
+        match kind {
+            LintKind::UnusedAlias => linter.unused_aliases(),
+            LintKind::UnrelatedText => linter.unrelated_texts(),
+            LintKind::SimilarTexts => linter.similar_texts(),
+            LintKind::SelfRelation => linter.self_relations(),
+            LintKind::ConflictingRelations => linter.conflicting_relations(),
+            LintKind::SingleUseTag => linter.single_use_tags(),
+        }
+    }
+    Ok(linter.result)
+}
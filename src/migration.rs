@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use fallible_iterator::FallibleIterator;
+use trove::{Object, ObjectId};
+
+use crate::content::Content;
+use crate::id_scheme::IdScheme;
+use crate::relation::Relation;
+use crate::text::Text;
+use crate::thesis::Thesis;
+use crate::write_transaction::WriteTransaction;
+
+fn with_references_replaced(content: Content, new_ids: &BTreeMap<ObjectId, ObjectId>) -> Content {
+    let new_id = |thesis_id: ObjectId| new_ids.get(&thesis_id).cloned().unwrap_or(thesis_id);
+    match content {
+        Content::Text(text) => Content::Text(Text {
+            references: text.references.into_iter().map(new_id).collect(),
+            ..text
+        }),
+        Content::Relation(relation) => Content::Relation(Relation {
+            from: new_id(relation.from),
+            kind: relation.kind,
+            to: new_id(relation.to),
+        }),
+    }
+}
+
+fn sorted_ids(theses: &BTreeMap<ObjectId, Thesis>) -> Result<Vec<ObjectId>> {
+    // 1 is being visited, 2 is visited
+    let mut states: BTreeMap<&ObjectId, u8> = BTreeMap::new();
+    let mut result = Vec::with_capacity(theses.len());
+    for root in theses.keys() {
+        let mut stack = vec![(root, false)];
+        while let Some((thesis_id, dependencies_visited)) = stack.pop() {
+            if dependencies_visited {
+                states.insert(thesis_id, 2);
+                result.push(thesis_id.clone());
+                continue;
+            }
+            if states.contains_key(thesis_id) {
+                continue;
+            }
+            states.insert(thesis_id, 1);
+            stack.push((thesis_id, true));
+            for referenced_thesis_id in theses[thesis_id].references() {
+                if let Some((referenced_thesis_id, _)) = theses.get_key_value(&referenced_thesis_id)
+                {
+                    match states.get(referenced_thesis_id) {
+                        None => stack.push((referenced_thesis_id, false)),
+                        Some(1) => {
+                            return Err(anyhow!(
+                                "Can not migrate theses: thesis {thesis_id:?} references itself \
+                                 through {referenced_thesis_id:?}"
+                            ));
+                        }
+                        Some(_) => {}
+                    }
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+// rewrites content of every thesis and computes it's id with given id scheme, dependencies
+// first, so that references of dependent theses point to new ids; theses which become equal are
+// merged, their tags are united; returns new ids of theses which ids changed
+pub fn rewrite_theses<F>(
+    transaction: &mut WriteTransaction,
+    id_scheme: &IdScheme,
+    rewrite: F,
+) -> Result<BTreeMap<ObjectId, ObjectId>>
+where
+    F: Fn(Content) -> Result<Content>,
+{
+    let mut theses = BTreeMap::new();
+    let mut objects = transaction.chest_transaction.objects()?;
+    while let Some(object) = objects.next()? {
+        theses.insert(object.id, serde_json::from_value::<Thesis>(object.value)?);
+    }
+    drop(objects);
+
+    let mut new_ids: BTreeMap<ObjectId, ObjectId> = BTreeMap::new();
+    let mut merged: BTreeMap<ObjectId, (Vec<ObjectId>, Thesis)> = BTreeMap::new();
+    let mut new_order = Vec::with_capacity(theses.len());
+    for thesis_id in sorted_ids(&theses)? {
+        let thesis = theses[&thesis_id].clone();
+        let new_thesis = Thesis {
+            content: rewrite(with_references_replaced(thesis.content, &new_ids))?,
+            ..thesis
+        };
+        let new_thesis_id = new_thesis.id_with(id_scheme)?;
+        if new_thesis_id != thesis_id {
+            new_ids.insert(thesis_id.clone(), new_thesis_id.clone());
+        }
+        if let Some((old_ids, existing)) = merged.get_mut(&new_thesis_id) {
+            match (&existing.alias, &new_thesis.alias) {
+                (Some(existing_alias), Some(alias)) if existing_alias != alias => {
+                    return Err(anyhow!(
+                        "Can not merge theses {old_ids:?} and {thesis_id:?} into {new_thesis_id:?} \
+                         as they have different aliases {existing_alias:?} and {alias:?}"
+                    ));
+                }
+                (None, Some(_)) => existing.alias = new_thesis.alias.clone(),
+                _ => {}
+            }
+            for tag in new_thesis.tags {
+                if !existing.tags.contains(&tag) {
+                    existing.tags.push(tag);
+                }
+            }
+            old_ids.push(thesis_id);
+        } else {
+            new_order.push(new_thesis_id.clone());
+            merged.insert(new_thesis_id, (vec![thesis_id], new_thesis));
+        }
+    }
+
+    for new_thesis_id in new_order {
+        let (old_ids, new_thesis) = merged.remove(&new_thesis_id).unwrap();
+        if old_ids == [new_thesis_id.clone()] {
+            continue;
+        }
+        for old_id in old_ids {
+            transaction.chest_transaction.remove(&old_id, &vec![])?;
+        }
+        transaction.chest_transaction.insert_with_id(Object {
+            id: new_thesis_id,
+            value: serde_json::to_value(new_thesis)?,
+        })?;
+    }
+    Ok(new_ids)
+}
+
+pub fn canonicalize(transaction: &mut WriteTransaction) -> Result<BTreeMap<ObjectId, ObjectId>> {
+    let id_scheme = transaction.sweater_config.id_scheme;
+    rewrite_theses(transaction, &id_scheme, |content| {
+        Ok(match content {
+            Content::Text(text) => Content::Text(text.canonicalized()?),
+            relation => relation,
+        })
+    })
+}
+
+// only transaction is switched to new id scheme, so it is run by `Sweater::change_id_scheme`,
+// which switches sweater too
+pub(crate) fn change_id_scheme(
+    transaction: &mut WriteTransaction,
+    id_scheme: IdScheme,
+) -> Result<BTreeMap<ObjectId, ObjectId>> {
+    let result = rewrite_theses(transaction, &id_scheme, Ok)?;
+    transaction.sweater_config.id_scheme = id_scheme;
+    Ok(result)
+}
@@ -0,0 +1,237 @@
+use std::borrow::Cow;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Error, Result};
+use fallible_iterator::FallibleIterator;
+use regex::Regex;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Paragraph {
+    pub source: Option<PathBuf>,
+    pub index: usize,
+    pub lines: Vec<String>,
+    pub lines_numbers: Vec<usize>,
+}
+
+impl Paragraph {
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+}
+
+impl std::fmt::Display for Paragraph {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}-th paragraph at line {}",
+            self.index + 1,
+            self.lines_numbers.first().unwrap_or(&0)
+        )?;
+        if let Some(ref source) = self.source {
+            write!(f, " of file {source:?}")?;
+        }
+        write!(f, " {:?}", self.text())
+    }
+}
+
+// ';' comment must start line and be followed by space, and ';' is not allowed in text, so
+// that text lines are never taken for comments
+pub fn is_comment(line: &str) -> bool {
+    line.trim_start().starts_with("//") || line == ";" || line.starts_with("; ")
+}
+
+pub struct ParagraphsIterator<'a> {
+    source: Option<PathBuf>,
+    lines_iterator: std::iter::Enumerate<std::str::Lines<'a>>,
+    paragraphs_count: usize,
+}
+
+impl<'a> ParagraphsIterator<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            source: None,
+            lines_iterator: input.lines().enumerate(),
+            paragraphs_count: 0,
+        }
+    }
+}
+
+// collects lines up to the next empty line skipping comments, the same for all inputs
+fn next_paragraph<'s, I>(
+    source: &Option<PathBuf>,
+    lines_iterator: I,
+    paragraphs_count: &mut usize,
+) -> Result<Option<Paragraph>>
+where
+    I: Iterator<Item = Result<(usize, Cow<'s, str>)>>,
+{
+    let mut lines = Vec::new();
+    let mut lines_numbers = Vec::new();
+    for line in lines_iterator {
+        let (line_index, line) = line?;
+        if is_comment(&line) {
+            continue;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            if lines.is_empty() {
+                continue;
+            }
+            break;
+        }
+        lines.push(line.to_string());
+        lines_numbers.push(line_index + 1);
+    }
+    Ok(if lines.is_empty() {
+        None
+    } else {
+        *paragraphs_count += 1;
+        Some(Paragraph {
+            source: source.clone(),
+            index: *paragraphs_count - 1,
+            lines,
+            lines_numbers,
+        })
+    })
+}
+
+impl<'a> FallibleIterator for ParagraphsIterator<'a> {
+    type Item = Paragraph;
+    type Error = Error;
+
+    fn next(&mut self) -> Result<Option<Self::Item>> {
+        next_paragraph(
+            &self.source,
+            self.lines_iterator
+                .by_ref()
+                .map(|(line_index, line)| Ok((line_index, Cow::Borrowed(line)))),
+            &mut self.paragraphs_count,
+        )
+    }
+}
+
+// reads lines incrementally, so only the current paragraph is kept in memory
+pub struct ReaderParagraphsIterator<R: BufRead> {
+    source: Option<PathBuf>,
+    lines_iterator: std::iter::Enumerate<std::io::Lines<R>>,
+    paragraphs_count: usize,
+}
+
+impl<R: BufRead> ReaderParagraphsIterator<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            source: None,
+            lines_iterator: reader.lines().enumerate(),
+            paragraphs_count: 0,
+        }
+    }
+}
+
+impl<R: BufRead> FallibleIterator for ReaderParagraphsIterator<R> {
+    type Item = Paragraph;
+    type Error = Error;
+
+    fn next(&mut self) -> Result<Option<Self::Item>> {
+        next_paragraph(
+            &self.source,
+            self.lines_iterator.by_ref().map(|(line_index, line)| {
+                line.map(|line| (line_index, Cow::Owned(line)))
+                    .with_context(|| format!("Can not read line {} of input", line_index + 1))
+            }),
+            &mut self.paragraphs_count,
+        )
+    }
+}
+
+pub struct FilesParagraphsIterator {
+    included_stack: Vec<(PathBuf, std::vec::IntoIter<Paragraph>)>,
+    already_included: Vec<PathBuf>,
+}
+
+impl FilesParagraphsIterator {
+    pub fn new(path: &Path) -> Result<Self> {
+        let mut result = Self {
+            included_stack: Vec::new(),
+            already_included: Vec::new(),
+        };
+        result.include(path)?;
+        Ok(result)
+    }
+
+    fn include(&mut self, path: &Path) -> Result<()> {
+        let canonical_path = path
+            .canonicalize()
+            .with_context(|| format!("Can not find commands file {path:?}"))?;
+        let input = std::fs::read_to_string(&canonical_path)
+            .with_context(|| format!("Can not read commands file {path:?}"))?;
+        let mut paragraphs_iterator = ParagraphsIterator::new(&input);
+        paragraphs_iterator.source = Some(path.to_path_buf());
+        let paragraphs = paragraphs_iterator.collect::<Vec<_>>()?;
+        self.already_included.push(canonical_path.clone());
+        self.included_stack
+            .push((canonical_path, paragraphs.into_iter()));
+        Ok(())
+    }
+
+    pub(crate) fn include_directive_path(paragraph: &Paragraph) -> Option<PathBuf> {
+        static INCLUDE_REGEX: std::sync::OnceLock<Regex> = std::sync::OnceLock::new();
+        let include_regex = INCLUDE_REGEX.get_or_init(|| {
+            Regex::new(r#"^ *include +(.+)$"#)
+                .with_context(|| "Can not compile regular expression for include directive")
+                .unwrap()
+        });
+        if paragraph.lines.len() == 1 {
+            include_regex
+                .captures(&paragraph.lines[0])
+                .map(|captures| PathBuf::from(&captures[1]))
+        } else {
+            None
+        }
+    }
+}
+
+impl FallibleIterator for FilesParagraphsIterator {
+    type Item = Paragraph;
+    type Error = Error;
+
+    fn next(&mut self) -> Result<Option<Self::Item>> {
+        while let Some((including_path, paragraphs)) = self.included_stack.last_mut() {
+            if let Some(paragraph) = paragraphs.next() {
+                if let Some(included_path) = Self::include_directive_path(&paragraph) {
+                    let included_path = including_path
+                        .parent()
+                        .map(|directory| directory.join(&included_path))
+                        .unwrap_or(included_path);
+                    let canonical_included_path =
+                        included_path.canonicalize().with_context(|| {
+                            format!("Can not find file {included_path:?} included in {paragraph}")
+                        })?;
+                    if let Some(cycle_start) = self
+                        .included_stack
+                        .iter()
+                        .position(|(path, _)| *path == canonical_included_path)
+                    {
+                        return Err(anyhow!(
+                            "Can not include file {included_path:?} in {paragraph}: include \
+                             cycle {:?}",
+                            self.included_stack[cycle_start..]
+                                .iter()
+                                .map(|(path, _)| path)
+                                .chain(std::iter::once(&canonical_included_path))
+                                .collect::<Vec<_>>()
+                        ));
+                    }
+                    if !self.already_included.contains(&canonical_included_path) {
+                        self.include(&included_path)
+                            .with_context(|| format!("Can not process include in {paragraph}"))?;
+                    }
+                    continue;
+                }
+                return Ok(Some(paragraph));
+            }
+            self.included_stack.pop();
+        }
+        Ok(None)
+    }
+}
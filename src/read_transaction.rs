@@ -1,11 +1,16 @@
+use std::collections::BTreeMap;
+
 use anyhow::{Error, Result};
 use fallible_iterator::FallibleIterator;
 use trove::{path_segments, IndexRecordType, ObjectId};
 
 use crate::alias::Alias;
+use crate::content::Content;
 use crate::sweater::SweaterConfig;
 use crate::thesis::Thesis;
 
+pub const MINIMAL_ID_PREFIX_LENGTH: usize = 4;
+
 pub struct ReadTransaction<'a> {
     pub chest_transaction: &'a trove::ReadTransaction<'a>,
     pub sweater_config: &'a SweaterConfig,
@@ -92,6 +97,63 @@ macro_rules! define_read_methods {
                     .map(|object| Ok(serde_json::from_value(object.value)?)),
             ))
         }
+
+        fn get_theses_ids_by_prefix(&self, prefix: &str) -> Result<Vec<ObjectId>> {
+            self.chest_transaction
+                .objects()?
+                .filter_map(|object| {
+                    Ok(if object.id.to_string().starts_with(prefix) {
+                        Some(object.id)
+                    } else {
+                        None
+                    })
+                })
+                .collect()
+        }
+
+        fn get_shortest_unambiguous_prefixes(
+            &self,
+        ) -> Result<std::collections::BTreeMap<ObjectId, String>> {
+            let mut theses_ids = self
+                .chest_transaction
+                .objects()?
+                .map(|object| Ok((object.id.to_string(), object.id)))
+                .collect::<Vec<_>>()?;
+            theses_ids.sort();
+            Ok($crate::read_transaction::shortest_unambiguous_prefixes(
+                theses_ids,
+            ))
+        }
+
+        // aliases index can only be selected by whole alias, so objects are scanned, but only
+        // their aliases are deserialized
+        fn get_aliases_by_prefix(&self, prefix: &str) -> Result<Vec<(Alias, ObjectId)>> {
+            let mut result = self
+                .chest_transaction
+                .objects()?
+                .filter_map(|object| {
+                    let alias: Option<Alias> = match object.value.get("alias") {
+                        Some(json_value) => serde_json::from_value(json_value.clone())?,
+                        None => None,
+                    };
+                    Ok(alias
+                        .filter(|alias| {
+                            alias.0 == prefix
+                                || alias
+                                    .0
+                                    .strip_prefix(prefix)
+                                    .is_some_and(|rest| rest.starts_with('.'))
+                        })
+                        .map(|alias| (alias, object.id)))
+                })
+                .collect::<Vec<_>>()?;
+            result.sort();
+            Ok(result)
+        }
+
+        fn sweater_config(&self) -> &SweaterConfig {
+            &self.sweater_config
+        }
     };
 }
 
@@ -101,6 +163,74 @@ pub trait ReadTransactionMethods<'a> {
     fn get_alias_by_thesis_id(&self, thesis_id: &ObjectId) -> Result<Option<Alias>>;
     fn where_referenced(&self, thesis_id: &ObjectId) -> Result<Vec<ObjectId>>;
     fn iter_theses(&self) -> Result<Box<dyn FallibleIterator<Item = Thesis, Error = Error> + '_>>;
+    fn get_theses_ids_by_prefix(&self, prefix: &str) -> Result<Vec<ObjectId>>;
+    fn get_shortest_unambiguous_prefixes(&self) -> Result<BTreeMap<ObjectId, String>>;
+    fn get_aliases_by_prefix(&self, prefix: &str) -> Result<Vec<(Alias, ObjectId)>>;
+    fn sweater_config(&self) -> &SweaterConfig;
+
+    fn thesis_id(&self, thesis: &Thesis) -> Result<ObjectId> {
+        self.content_id(&thesis.content)
+    }
+
+    fn content_id(&self, content: &Content) -> Result<ObjectId> {
+        content.id_with(&self.sweater_config().id_scheme)
+    }
+
+    fn get_shortest_unambiguous_prefix(&self, thesis_id: &ObjectId) -> Result<String> {
+        let thesis_id_string = thesis_id.to_string();
+        let minimal_length = MINIMAL_ID_PREFIX_LENGTH.min(thesis_id_string.len());
+        let length = self
+            .get_theses_ids_by_prefix(&thesis_id_string[..minimal_length])?
+            .iter()
+            .map(|other_id| other_id.to_string())
+            .filter(|other_id_string| *other_id_string != thesis_id_string)
+            .map(|other_id_string| common_prefix_length(&thesis_id_string, &other_id_string) + 1)
+            .fold(minimal_length, usize::max)
+            .min(thesis_id_string.len());
+        Ok(thesis_id_string[..length].to_string())
+    }
+
+    // names to show theses by: alias if thesis has one, otherwise shortest unambiguous id prefix
+    // marked with ~ the same way it is referenced
+    fn get_theses_names(&self) -> Result<BTreeMap<ObjectId, String>> {
+        let mut result = self.get_shortest_unambiguous_prefixes()?;
+        for (thesis_id, name) in result.iter_mut() {
+            *name = match self.get_alias_by_thesis_id(thesis_id)? {
+                Some(alias) => alias.0,
+                None => format!("~{name}"),
+            };
+        }
+        Ok(result)
+    }
+}
+
+fn common_prefix_length(first: &str, second: &str) -> usize {
+    first
+        .bytes()
+        .zip(second.bytes())
+        .take_while(|(first_byte, second_byte)| first_byte == second_byte)
+        .count()
+}
+
+// theses ids must be sorted by their strings, so each id shares the longest prefix with one of
+// its neighbours
+pub fn shortest_unambiguous_prefixes(
+    theses_ids: Vec<(String, ObjectId)>,
+) -> BTreeMap<ObjectId, String> {
+    let mut result = BTreeMap::new();
+    for (index, (thesis_id_string, thesis_id)) in theses_ids.iter().enumerate() {
+        let length = [index.checked_sub(1), Some(index + 1)]
+            .into_iter()
+            .flatten()
+            .filter_map(|neighbour_index| theses_ids.get(neighbour_index))
+            .map(|(neighbour_string, _)| {
+                common_prefix_length(thesis_id_string, neighbour_string) + 1
+            })
+            .fold(MINIMAL_ID_PREFIX_LENGTH, usize::max)
+            .min(thesis_id_string.len());
+        result.insert(thesis_id.clone(), thesis_id_string[..length].to_string());
+    }
+    result
 }
 
 impl<'a> ReadTransactionMethods<'a> for ReadTransaction<'a> {
@@ -26,6 +26,15 @@ impl RelationKind {
     }
 }
 
+// form in which relation kind is written by exporters: canonical one as in supported relations
+// kinds or the first of it's shorthands, falling back to canonical one if there are no shorthands
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RelationKindForm {
+    #[default]
+    Canonical,
+    Shorthand,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, bincode::Encode, PartialEq, Eq)]
 pub struct Relation {
     pub from: ObjectId,
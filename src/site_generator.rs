@@ -0,0 +1,324 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use anyhow::{Context, Error, Result};
+use fallible_iterator::FallibleIterator;
+use serde::{Deserialize, Serialize};
+use trove::ObjectId;
+
+use crate::alias::Alias;
+use crate::content::Content;
+use crate::read_transaction::ReadTransactionMethods;
+use crate::relation::RelationKind;
+use crate::tag::Tag;
+use crate::text::TextPart;
+use crate::thesis::Thesis;
+
+#[derive(Serialize, Deserialize)]
+pub struct SiteGeneratorConfig {
+    pub title: String,
+}
+
+pub struct Page {
+    pub file_name: String,
+    pub content: String,
+}
+
+pub enum Stage {
+    Theses,
+    TagsPages,
+    Indices,
+    Finished,
+}
+
+pub struct SiteGenerator<'a> {
+    pub config: &'a SiteGeneratorConfig,
+    pub read_able_transaction: &'a dyn ReadTransactionMethods<'a>,
+    pub theses_iterator: Box<dyn FallibleIterator<Item = Thesis, Error = Error> + 'a>,
+    pub stage: Stage,
+    pub theses_names: BTreeMap<ObjectId, String>,
+    pub theses_titles: Vec<(String, ObjectId)>,
+    pub tagged: BTreeMap<Tag, Vec<ObjectId>>,
+    pub aliases: BTreeMap<Alias, ObjectId>,
+    pub tags_counts: Vec<(Tag, usize)>,
+    pub pending_pages: Vec<Page>,
+}
+
+impl<'a> SiteGenerator<'a> {
+    pub fn new(
+        config: &'a SiteGeneratorConfig,
+        read_able_transaction: &'a dyn ReadTransactionMethods<'a>,
+    ) -> Result<Self> {
+        Ok(Self {
+            config,
+            read_able_transaction,
+            theses_iterator: Box::new(read_able_transaction.iter_theses()?),
+            stage: Stage::Theses,
+            theses_names: read_able_transaction.get_theses_names()?,
+            theses_titles: Vec::new(),
+            tagged: BTreeMap::new(),
+            aliases: BTreeMap::new(),
+            tags_counts: Vec::new(),
+            pending_pages: Vec::new(),
+        })
+    }
+
+    pub fn write(mut self, directory: &Path) -> Result<()> {
+        std::fs::create_dir_all(directory)
+            .with_context(|| format!("Can not create site directory {directory:?}"))?;
+        while let Some(page) = self.next()? {
+            let page_path = directory.join(&page.file_name);
+            std::fs::write(&page_path, page.content)
+                .with_context(|| format!("Can not write site page {page_path:?}"))?;
+        }
+        Ok(())
+    }
+
+    pub fn thesis_file_name(thesis_id: &ObjectId) -> String {
+        format!("thesis-{thesis_id}.html")
+    }
+
+    pub fn tag_file_name(tag: &Tag) -> String {
+        format!("tag-{}.html", tag.0)
+    }
+
+    fn page(&self, title: &str, body: &str) -> String {
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{} - {}</title>\n\
+             </head>\n<body>\n<nav><a href=\"index.html\">{}</a> | <a \
+             href=\"aliases.html\">aliases</a> | <a href=\"tags.html\">tags</a></nav>\n<h1>{}</h1>\n\
+             {body}\n</body>\n</html>\n",
+            html_escape::encode_text(title),
+            html_escape::encode_text(&self.config.title),
+            html_escape::encode_text(&self.config.title),
+            html_escape::encode_text(title),
+        )
+    }
+
+    fn title(&self, thesis_id: &ObjectId) -> String {
+        self.theses_names
+            .get(thesis_id)
+            .cloned()
+            .unwrap_or_else(|| thesis_id.to_string())
+    }
+
+    fn link(&self, thesis_id: &ObjectId) -> String {
+        format!(
+            "<a href=\"{}\">[{}]</a>",
+            Self::thesis_file_name(thesis_id),
+            html_escape::encode_text(&self.title(thesis_id))
+        )
+    }
+
+    fn list(items: &[String]) -> String {
+        format!(
+            "<ul>\n{}</ul>",
+            items
+                .iter()
+                .map(|item| format!("<li>{item}</li>\n"))
+                .collect::<Vec<_>>()
+                .concat()
+        )
+    }
+
+    fn thesis_page(&self, thesis: &Thesis) -> Result<Page> {
+        let thesis_id = self.read_able_transaction.thesis_id(thesis)?;
+        let mut body = format!("<p><code>{thesis_id}</code></p>\n");
+        match thesis.content {
+            Content::Text(ref text) => {
+                let mut rendered = Vec::new();
+                for part in text.parts() {
+                    rendered.push(match part {
+                        TextPart::Raw(raw_text) => {
+                            html_escape::encode_text(&raw_text.0).to_string()
+                        }
+                        TextPart::Reference(reference, None) => self.link(reference),
+                        TextPart::Reference(reference, Some(label)) => format!(
+                            "<a href=\"{}\">{}</a>",
+                            Self::thesis_file_name(reference),
+                            html_escape::encode_text(&label.0)
+                        ),
+                    });
+                }
+                body.push_str(&format!("<p>{}</p>\n", rendered.concat()));
+            }
+            Content::Relation(ref relation) => {
+                body.push_str(&format!(
+                    "<p>{} <em>{}</em> {}</p>\n",
+                    self.link(&relation.from),
+                    html_escape::encode_text(
+                        self.read_able_transaction
+                            .sweater_config()
+                            .exported_relation_kind(&relation.kind)
+                    ),
+                    self.link(&relation.to)
+                ));
+            }
+        }
+        if !thesis.tags.is_empty() {
+            body.push_str(&format!(
+                "<p>{}</p>\n",
+                thesis
+                    .tags
+                    .iter()
+                    .map(|tag| format!(
+                        "<a href=\"{}\">#{}</a>",
+                        Self::tag_file_name(tag),
+                        html_escape::encode_text(&tag.0)
+                    ))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ));
+        }
+
+        let mut outgoing: BTreeMap<RelationKind, Vec<String>> = BTreeMap::new();
+        let mut incoming: BTreeMap<RelationKind, Vec<String>> = BTreeMap::new();
+        let mut backlinks = Vec::new();
+        let referencing_theses_ids = self
+            .read_able_transaction
+            .where_referenced(&thesis_id)?
+            .into_iter()
+            .collect::<BTreeSet<_>>();
+        for referencing_thesis_id in referencing_theses_ids {
+            if let Some(referencing_thesis) = self
+                .read_able_transaction
+                .get_thesis(&referencing_thesis_id)?
+            {
+                match referencing_thesis.content {
+                    Content::Text(_) => backlinks.push(self.link(&referencing_thesis_id)),
+                    Content::Relation(relation) => {
+                        if relation.from == thesis_id {
+                            outgoing
+                                .entry(relation.kind.clone())
+                                .or_default()
+                                .push(format!(
+                                    "{} ({})",
+                                    self.link(&relation.to),
+                                    self.link(&referencing_thesis_id)
+                                ));
+                        }
+                        if relation.to == thesis_id {
+                            incoming.entry(relation.kind).or_default().push(format!(
+                                "{} ({})",
+                                self.link(&relation.from),
+                                self.link(&referencing_thesis_id)
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        for (header, grouped) in [
+            ("Outgoing relations", outgoing),
+            ("Incoming relations", incoming),
+        ] {
+            if !grouped.is_empty() {
+                body.push_str(&format!("<h2>{header}</h2>\n"));
+                for (relation_kind, items) in grouped {
+                    body.push_str(&format!(
+                        "<h3>{}</h3>\n{}\n",
+                        html_escape::encode_text(
+                            self.read_able_transaction
+                                .sweater_config()
+                                .exported_relation_kind(&relation_kind)
+                        ),
+                        Self::list(&items)
+                    ));
+                }
+            }
+        }
+        if !backlinks.is_empty() {
+            body.push_str(&format!(
+                "<h2>Referenced in</h2>\n{}\n",
+                Self::list(&backlinks)
+            ));
+        }
+
+        Ok(Page {
+            file_name: Self::thesis_file_name(&thesis_id),
+            content: self.page(&self.title(&thesis_id), &body),
+        })
+    }
+}
+
+impl<'a> FallibleIterator for SiteGenerator<'a> {
+    type Item = Page;
+    type Error = Error;
+
+    fn next(&mut self) -> Result<Option<Self::Item>> {
+        loop {
+            match self.stage {
+                Stage::Theses => {
+                    if let Some(thesis) = self.theses_iterator.next()? {
+                        let thesis_id = self.read_able_transaction.thesis_id(&thesis)?;
+                        for tag in thesis.tags.iter() {
+                            self.tagged
+                                .entry(tag.clone())
+                                .or_default()
+                                .push(thesis_id.clone());
+                        }
+                        if let Some(ref alias) = thesis.alias {
+                            self.aliases.insert(alias.clone(), thesis_id.clone());
+                        }
+                        self.theses_titles
+                            .push((self.title(&thesis_id), thesis_id.clone()));
+                        return Ok(Some(self.thesis_page(&thesis)?));
+                    }
+                    self.stage = Stage::TagsPages;
+                }
+                Stage::TagsPages => {
+                    if let Some((tag, theses_ids)) = self.tagged.pop_first() {
+                        let mut items = Vec::new();
+                        for thesis_id in theses_ids.iter() {
+                            items.push(self.link(thesis_id));
+                        }
+                        self.tags_counts.push((tag.clone(), theses_ids.len()));
+                        return Ok(Some(Page {
+                            file_name: Self::tag_file_name(&tag),
+                            content: self.page(&format!("#{}", tag.0), &Self::list(&items)),
+                        }));
+                    }
+                    self.stage = Stage::Indices;
+                }
+                Stage::Indices => {
+                    self.stage = Stage::Finished;
+                    let tags_items = self
+                        .tags_counts
+                        .iter()
+                        .map(|(tag, count)| {
+                            format!(
+                                "<a href=\"{}\">#{}</a> ({count})",
+                                Self::tag_file_name(tag),
+                                html_escape::encode_text(&tag.0)
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    let mut aliases_items = Vec::new();
+                    for thesis_id in self.aliases.values() {
+                        aliases_items.push(self.link(thesis_id));
+                    }
+                    self.theses_titles.sort();
+                    let mut theses_items = Vec::new();
+                    for (_, thesis_id) in self.theses_titles.iter() {
+                        theses_items.push(self.link(thesis_id));
+                    }
+                    self.pending_pages = vec![
+                        Page {
+                            file_name: "index.html".to_string(),
+                            content: self.page(&self.config.title, &Self::list(&theses_items)),
+                        },
+                        Page {
+                            file_name: "aliases.html".to_string(),
+                            content: self.page("aliases", &Self::list(&aliases_items)),
+                        },
+                        Page {
+                            file_name: "tags.html".to_string(),
+                            content: self.page("tags", &Self::list(&tags_items)),
+                        },
+                    ];
+                }
+                Stage::Finished => return Ok(self.pending_pages.pop()),
+            }
+        }
+    }
+}
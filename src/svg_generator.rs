@@ -0,0 +1,256 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Error, Result};
+use fallible_iterator::FallibleIterator;
+use serde::{Deserialize, Serialize};
+use trove::ObjectId;
+
+use crate::content::Content;
+use crate::graph_generator::wrap_lines;
+use crate::layout::{Layout, LayoutConfig, Point, Size};
+use crate::read_transaction::ReadTransactionMethods;
+
+#[derive(Serialize, Deserialize)]
+pub struct SvgGeneratorConfig {
+    pub wrap_width: u16,
+    pub font_size: f64,
+    #[serde(default)]
+    pub layout: LayoutConfig,
+}
+
+#[derive(PartialEq, Eq)]
+enum NodeStyle {
+    Text,
+    Relation,
+}
+
+struct Node {
+    id: String,
+    style: NodeStyle,
+    header: String,
+    body_lines: Vec<String>,
+    size: Size,
+}
+
+#[derive(PartialEq, Eq)]
+enum EdgeStyle {
+    RelationFrom,
+    RelationTo,
+    Reference,
+}
+
+pub struct SvgGenerator {
+    pub elements: std::vec::IntoIter<String>,
+}
+
+impl SvgGenerator {
+    const PADDING: f64 = 8.0;
+
+    pub fn new<'a>(
+        config: &'a SvgGeneratorConfig,
+        read_able_transaction: &'a dyn ReadTransactionMethods<'a>,
+    ) -> Result<Self> {
+        let line_height = config.font_size * 1.4;
+        let char_width = config.font_size * 0.6;
+
+        let mut nodes: Vec<Node> = Vec::new();
+        let mut nodes_indices: BTreeMap<ObjectId, usize> = BTreeMap::new();
+        let mut edges: Vec<(ObjectId, ObjectId, EdgeStyle)> = Vec::new();
+        let theses_names = read_able_transaction.get_theses_names()?;
+        let mut theses_iterator = read_able_transaction.iter_theses()?;
+        while let Some(thesis) = theses_iterator.next()? {
+            let thesis_id = read_able_transaction.thesis_id(&thesis)?;
+            let header = theses_names
+                .get(&thesis_id)
+                .cloned()
+                .unwrap_or_else(|| thesis_id.to_string());
+            let (style, body_lines) = match thesis.content {
+                Content::Text(ref text) => {
+                    for referenced_thesis_id in thesis.references() {
+                        edges.push((
+                            thesis_id.clone(),
+                            referenced_thesis_id,
+                            EdgeStyle::Reference,
+                        ));
+                    }
+                    (
+                        NodeStyle::Text,
+                        wrap_lines(
+                            &text.composed_with_names(&theses_names),
+                            config.wrap_width as usize,
+                        ),
+                    )
+                }
+                Content::Relation(ref relation) => {
+                    edges.push((
+                        relation.from.clone(),
+                        thesis_id.clone(),
+                        EdgeStyle::RelationFrom,
+                    ));
+                    edges.push((
+                        thesis_id.clone(),
+                        relation.to.clone(),
+                        EdgeStyle::RelationTo,
+                    ));
+                    (
+                        NodeStyle::Relation,
+                        vec![read_able_transaction
+                            .sweater_config()
+                            .exported_relation_kind(&relation.kind)
+                            .to_string()],
+                    )
+                }
+            };
+            let widest_line_length = body_lines
+                .iter()
+                .chain(std::iter::once(&header))
+                .map(|line| line.chars().count())
+                .max()
+                .unwrap_or(0);
+            let size = Size {
+                width: widest_line_length as f64 * char_width + 2.0 * Self::PADDING,
+                height: (body_lines.len() + 1) as f64 * line_height + 4.0 * Self::PADDING,
+            };
+            nodes_indices.insert(thesis_id.clone(), nodes.len());
+            nodes.push(Node {
+                id: thesis_id.to_string(),
+                style,
+                header,
+                body_lines,
+                size,
+            });
+        }
+
+        let edges: Vec<(usize, usize, EdgeStyle)> = edges
+            .into_iter()
+            .filter_map(|(from, to, style)| {
+                Some((*nodes_indices.get(&from)?, *nodes_indices.get(&to)?, style))
+            })
+            .collect();
+        let layout = Layout::new(
+            &nodes.iter().map(|node| node.size).collect::<Vec<_>>(),
+            &edges
+                .iter()
+                .map(|(from, to, _)| (*from, *to))
+                .collect::<Vec<_>>(),
+            &config.layout,
+        );
+
+        let mut elements = vec![format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{:.0}" height="{:.0}" viewBox="0 0 {:.0} {:.0}" font-family="monospace" font-size="{}">"#,
+            layout.width, layout.height, layout.width, layout.height, config.font_size
+        )];
+        elements.push(
+            concat!(
+                "\n\t<defs>",
+                r#"<marker id="arrow" viewBox="0 0 10 10" refX="10" refY="5" markerWidth="8" markerHeight="8" orient="auto"><path d="M 0 0 L 10 5 L 0 10 z"/></marker>"#,
+                r#"<marker id="tee" viewBox="0 0 10 10" refX="1" refY="5" markerWidth="8" markerHeight="8" orient="auto"><path d="M 1 0 L 1 10" stroke="black" stroke-width="2"/></marker>"#,
+                "</defs>"
+            )
+            .to_string(),
+        );
+        for ((from, to, style), path) in edges.iter().zip(layout.edges_paths.iter()) {
+            let mut points = path.clone();
+            if points.len() < 2 {
+                continue;
+            }
+            let last = points.len() - 1;
+            points[0] = Self::clipped(points[0], points[1], nodes[*from].size);
+            points[last] = Self::clipped(points[last], points[last - 1], nodes[*to].size);
+            elements.push(format!(
+                "\n\t<path d=\"{}\" fill=\"none\" {}/>",
+                points
+                    .iter()
+                    .enumerate()
+                    .map(|(point_index, point)| format!(
+                        "{} {:.1} {:.1}",
+                        if point_index == 0 { "M" } else { "L" },
+                        point.x,
+                        point.y
+                    ))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                match style {
+                    EdgeStyle::RelationFrom => r#"stroke="black" marker-start="url(#tee)""#,
+                    EdgeStyle::RelationTo => r#"stroke="black" marker-end="url(#arrow)""#,
+                    EdgeStyle::Reference => r#"stroke="grey" stroke-dasharray="2 4""#,
+                }
+            ));
+        }
+        for (node, center) in nodes.iter().zip(layout.nodes_centers.iter()) {
+            let left = center.x - node.size.width / 2.0;
+            let top = center.y - node.size.height / 2.0;
+            let separator_y = top + line_height + 2.0 * Self::PADDING;
+            let (border, separator) = match node.style {
+                NodeStyle::Text => (r#"stroke-width="2""#, ""),
+                NodeStyle::Relation => (
+                    r#"stroke-width="1" stroke-dasharray="4 2""#,
+                    r#" stroke-dasharray="4 2""#,
+                ),
+            };
+            let mut node_element = format!(
+                "\n\t<g id=\"{}\">\n\t\t<rect x=\"{left:.1}\" y=\"{top:.1}\" width=\"{:.1}\" \
+                 height=\"{:.1}\" fill=\"white\" stroke=\"black\" {border}/>\n\t\t<line \
+                 x1=\"{left:.1}\" y1=\"{separator_y:.1}\" x2=\"{:.1}\" y2=\"{separator_y:.1}\" \
+                 stroke=\"black\"{separator}/>\n\t\t<text x=\"{:.1}\" y=\"{:.1}\" \
+                 text-anchor=\"middle\">{}</text>",
+                node.id,
+                node.size.width,
+                node.size.height,
+                left + node.size.width,
+                center.x,
+                top + Self::PADDING + line_height * 0.75,
+                html_escape::encode_text(&node.header)
+            );
+            for (line_index, line) in node.body_lines.iter().enumerate() {
+                node_element.push_str(&format!(
+                    "\n\t\t<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+                    center.x,
+                    separator_y + Self::PADDING + line_height * (line_index as f64 + 0.75),
+                    html_escape::encode_text(line)
+                ));
+            }
+            node_element.push_str("\n\t</g>");
+            elements.push(node_element);
+        }
+        elements.push("\n</svg>\n".to_string());
+
+        Ok(Self {
+            elements: elements.into_iter(),
+        })
+    }
+
+    // moves point from node center to the node border in the direction of the next point
+    fn clipped(center: Point, towards: Point, size: Size) -> Point {
+        let dx = towards.x - center.x;
+        let dy = towards.y - center.y;
+        if dx == 0.0 && dy == 0.0 {
+            return center;
+        }
+        let scale = f64::min(
+            if dx == 0.0 {
+                f64::INFINITY
+            } else {
+                size.width / 2.0 / dx.abs()
+            },
+            if dy == 0.0 {
+                f64::INFINITY
+            } else {
+                size.height / 2.0 / dy.abs()
+            },
+        );
+        Point {
+            x: center.x + dx * scale,
+            y: center.y + dy * scale,
+        }
+    }
+}
+
+impl FallibleIterator for SvgGenerator {
+    type Item = String;
+    type Error = Error;
+
+    fn next(&mut self) -> Result<Option<Self::Item>> {
+        Ok(self.elements.next())
+    }
+}
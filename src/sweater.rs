@@ -1,17 +1,84 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
+use fallible_iterator::FallibleIterator;
 use serde::{Deserialize, Serialize};
-use trove::{Chest, ChestConfig};
+use trove::{Chest, ChestConfig, ObjectId};
 
+use crate::id_scheme::IdScheme;
+use crate::migration;
 use crate::read_transaction::ReadTransaction;
-use crate::relation::RelationKind;
+use crate::relation::{RelationKind, RelationKindForm};
+use crate::svg_generator::{SvgGenerator, SvgGeneratorConfig};
+use crate::text::TextCharset;
 use crate::write_transaction::WriteTransaction;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SweaterConfig {
     pub chest: ChestConfig,
     pub supported_relations_kinds: BTreeSet<RelationKind>,
+    #[serde(default)]
+    pub text_charset: TextCharset,
+    #[serde(default)]
+    pub id_scheme: IdScheme,
+    // alternative names of supported relations kinds accepted in commands, e.g. "=>" for
+    // "therefore"
+    #[serde(default)]
+    pub relations_kinds_shorthands: BTreeMap<RelationKind, Vec<String>>,
+    #[serde(default)]
+    pub exported_relation_kind_form: RelationKindForm,
+}
+
+impl SweaterConfig {
+    // supported relation kind given by it's canonical name or shorthand
+    pub fn relation_kind(&self, name: &str) -> Option<RelationKind> {
+        let relation_kind = RelationKind(name.to_string());
+        if self.supported_relations_kinds.contains(&relation_kind) {
+            return Some(relation_kind);
+        }
+        self.relations_kinds_shorthands
+            .iter()
+            .find(|(_, shorthands)| shorthands.iter().any(|shorthand| shorthand == name))
+            .map(|(relation_kind, _)| relation_kind.clone())
+    }
+
+    pub fn exported_relation_kind<'a>(&'a self, relation_kind: &'a RelationKind) -> &'a str {
+        match self.exported_relation_kind_form {
+            RelationKindForm::Canonical => &relation_kind.0,
+            RelationKindForm::Shorthand => self
+                .relations_kinds_shorthands
+                .get(relation_kind)
+                .and_then(|shorthands| shorthands.first())
+                .unwrap_or(&relation_kind.0),
+        }
+    }
+
+    pub fn validated(&self) -> Result<&Self> {
+        self.text_charset.regex()?;
+        let mut names: BTreeMap<&str, &RelationKind> = self
+            .supported_relations_kinds
+            .iter()
+            .map(|relation_kind| (relation_kind.0.as_str(), relation_kind))
+            .collect();
+        for (relation_kind, shorthands) in self.relations_kinds_shorthands.iter() {
+            if !self.supported_relations_kinds.contains(relation_kind) {
+                return Err(anyhow!(
+                    "Can not use shorthands {shorthands:?} for relation kind {relation_kind:?} as \
+                     it is not in supported relations kinds {:?}",
+                    self.supported_relations_kinds
+                ));
+            }
+            for shorthand in shorthands {
+                if let Some(existing_relation_kind) = names.insert(shorthand, relation_kind) {
+                    return Err(anyhow!(
+                        "Can not use shorthand {shorthand:?} for relation kind {relation_kind:?} \
+                         as it already names relation kind {existing_relation_kind:?}"
+                    ));
+                }
+            }
+        }
+        Ok(self)
+    }
 }
 
 pub struct Sweater {
@@ -21,6 +88,7 @@ pub struct Sweater {
 
 impl Sweater {
     pub fn new(config: SweaterConfig) -> Result<Self> {
+        config.validated()?;
         Ok(Self {
             chest: Chest::new(config.chest.clone()).with_context(|| {
                 format!(
@@ -61,4 +129,28 @@ impl Sweater {
                 "Can not lock all write operations on chest and initiate read transaction"
             })
     }
+
+    // rewrites chest with theses identified by given id scheme and switches sweater to it, so that
+    // following transactions identify theses the same way; sweater configuration file still must
+    // be changed to the new scheme for chest to be opened with it later
+    pub fn change_id_scheme(
+        &mut self,
+        id_scheme: IdScheme,
+    ) -> Result<BTreeMap<ObjectId, ObjectId>> {
+        let result = self.lock_all_and_write(|transaction| {
+            migration::change_id_scheme(transaction, id_scheme)
+        })?;
+        self.config.id_scheme = id_scheme;
+        Ok(result)
+    }
+
+    pub fn render_svg(&self, config: &SvgGeneratorConfig, path: &std::path::Path) -> Result<()> {
+        let svg = self.lock_all_writes_and_read(|transaction| {
+            SvgGenerator::new(config, &transaction)?
+                .collect::<Vec<_>>()
+                .map(|elements| elements.join(""))
+        })?;
+        std::fs::write(path, svg)
+            .with_context(|| format!("Can not write sweater SVG rendering to {path:?}"))
+    }
 }
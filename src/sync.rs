@@ -0,0 +1,178 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{anyhow, Result};
+use fallible_iterator::FallibleIterator;
+use trove::ObjectId;
+
+use crate::commands::Command;
+use crate::read_transaction::ReadTransactionMethods;
+use crate::thesis::Thesis;
+use crate::write_transaction::WriteTransaction;
+
+// theses state described by commands applied to empty sweater, in order of addition so that
+// referenced theses go before theses referencing them
+#[derive(Default)]
+pub(crate) struct DesiredState {
+    pub(crate) theses: BTreeMap<ObjectId, Thesis>,
+    pub(crate) order: Vec<ObjectId>,
+}
+
+impl DesiredState {
+    fn get_mut(&mut self, thesis_id: &ObjectId, command: &Command) -> Result<&mut Thesis> {
+        self.theses.get_mut(thesis_id).ok_or_else(|| {
+            anyhow!(
+                "Can not compute desired state as command {command:?} refers thesis \
+                 {thesis_id:?} which is not added by previous commands"
+            )
+        })
+    }
+
+    fn remove(&mut self, thesis_id: &ObjectId) {
+        if self.theses.remove(thesis_id).is_some() {
+            let where_referenced = self
+                .theses
+                .iter()
+                .filter(|(_, thesis)| thesis.references().contains(thesis_id))
+                .map(|(referencing_thesis_id, _)| referencing_thesis_id.clone())
+                .collect::<Vec<_>>();
+            for referencing_thesis_id in where_referenced {
+                self.remove(&referencing_thesis_id);
+            }
+        }
+    }
+
+    pub(crate) fn apply<'a>(
+        &mut self,
+        read_able_transaction: &dyn ReadTransactionMethods<'a>,
+        command: &Command,
+    ) -> Result<()> {
+        match command {
+            Command::AddThesis(thesis) => {
+                let thesis_id = read_able_transaction.thesis_id(thesis)?;
+                if let Some(existing) = self.theses.get_mut(&thesis_id) {
+                    for tag in thesis.tags.iter() {
+                        if !existing.tags.contains(tag) {
+                            existing.tags.push(tag.clone());
+                        }
+                    }
+                    if thesis.alias.is_some() {
+                        existing.alias = thesis.alias.clone();
+                    }
+                } else {
+                    self.order.push(thesis_id.clone());
+                    self.theses.insert(thesis_id, thesis.clone());
+                }
+            }
+            Command::RemoveThesis(thesis_id) => self.remove(thesis_id),
+            Command::AddTags(thesis_id, tags) => {
+                let thesis = self.get_mut(thesis_id, command)?;
+                for tag in tags {
+                    if !thesis.tags.contains(tag) {
+                        thesis.tags.push(tag.clone());
+                    }
+                }
+            }
+            Command::RemoveTags(thesis_id, tags) => {
+                self.get_mut(thesis_id, command)?
+                    .tags
+                    .retain(|tag| !tags.contains(tag));
+            }
+            Command::SetAlias(thesis_id, alias) => {
+                self.get_mut(thesis_id, command)?.alias = Some(alias.clone());
+            }
+        }
+        Ok(())
+    }
+}
+
+// computes commands which make sweater contain exactly theses described by desired commands,
+// like ones parsed with CommandsIterator from full commands file; removals go first, then tags
+// and aliases changes of kept theses, then additions; removal of thesis removes relations and
+// theses referencing it, so they are not removed explicitly; alias can not be unset, so alias of
+// kept thesis is left as is if desired thesis has no alias; existing theses referenced by desired
+// ones are kept even if desired commands do not add them
+pub fn plan<'a>(
+    read_able_transaction: &dyn ReadTransactionMethods<'a>,
+    desired_commands: &[Command],
+) -> Result<Vec<Command>> {
+    let mut desired = DesiredState::default();
+    for command in desired_commands {
+        desired.apply(read_able_transaction, command)?;
+    }
+
+    let mut current = BTreeMap::new();
+    let mut theses = read_able_transaction.iter_theses()?;
+    while let Some(thesis) = theses.next()? {
+        current.insert(read_able_transaction.thesis_id(&thesis)?, thesis);
+    }
+    drop(theses);
+
+    // desired theses may reference existing theses not described by desired commands, which are
+    // kept with everything they reference, as removing them would remove desired theses too
+    let mut kept = desired.theses.keys().cloned().collect::<BTreeSet<_>>();
+    let mut referenced = desired
+        .theses
+        .values()
+        .flat_map(|thesis| thesis.references())
+        .collect::<Vec<_>>();
+    while let Some(thesis_id) = referenced.pop() {
+        if kept.insert(thesis_id.clone())
+            && let Some(thesis) = current.get(&thesis_id)
+        {
+            referenced.extend(thesis.references());
+        }
+    }
+
+    let mut result = Vec::new();
+    for (thesis_id, thesis) in current.iter() {
+        if !kept.contains(thesis_id)
+            && !thesis.references().iter().any(|referenced_thesis_id| {
+                current.contains_key(referenced_thesis_id) && !kept.contains(referenced_thesis_id)
+            })
+        {
+            result.push(Command::RemoveThesis(thesis_id.clone()));
+        }
+    }
+    for (thesis_id, thesis) in current.iter() {
+        if let Some(desired_thesis) = desired.theses.get(thesis_id) {
+            let tags_to_remove = thesis
+                .tags
+                .iter()
+                .filter(|tag| !desired_thesis.tags.contains(tag))
+                .cloned()
+                .collect::<Vec<_>>();
+            if !tags_to_remove.is_empty() {
+                result.push(Command::RemoveTags(thesis_id.clone(), tags_to_remove));
+            }
+            let tags_to_add = desired_thesis
+                .tags
+                .iter()
+                .filter(|tag| !thesis.tags.contains(tag))
+                .cloned()
+                .collect::<Vec<_>>();
+            if !tags_to_add.is_empty() {
+                result.push(Command::AddTags(thesis_id.clone(), tags_to_add));
+            }
+            if let Some(ref alias) = desired_thesis.alias
+                && thesis.alias.as_ref() != Some(alias)
+            {
+                result.push(Command::SetAlias(thesis_id.clone(), alias.clone()));
+            }
+        }
+    }
+    for thesis_id in desired.order.iter() {
+        if !current.contains_key(thesis_id)
+            && let Some(thesis) = desired.theses.get(thesis_id)
+        {
+            result.push(Command::AddThesis(thesis.clone()));
+        }
+    }
+    Ok(result)
+}
+
+pub fn apply(transaction: &mut WriteTransaction, plan: &[Command]) -> Result<()> {
+    for command in plan {
+        transaction.execute_command(command)?;
+    }
+    Ok(())
+}
@@ -1,36 +1,102 @@
+use std::collections::BTreeMap;
+
 use anyhow::{anyhow, Context, Result};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use trove::ObjectId;
+use unicode_normalization::UnicodeNormalization;
 
 use crate::alias::Alias;
 use crate::aliases_resolver::AliasesResolver;
 use crate::commands::Reference;
 use crate::read_transaction::ReadTransactionMethods;
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TextCharset {
+    pub scripts: Vec<String>,
+    pub punctuation: String,
+    #[serde(skip)]
+    regex: std::sync::OnceLock<Regex>,
+}
+
+impl Default for TextCharset {
+    fn default() -> Self {
+        Self::new(
+            vec!["Cyrillic".to_string(), "Latin".to_string()],
+            ",-:.'\"".to_string(),
+        )
+    }
+}
+
+impl TextCharset {
+    pub fn new(scripts: Vec<String>, punctuation: String) -> Self {
+        Self {
+            scripts,
+            punctuation,
+            regex: std::sync::OnceLock::new(),
+        }
+    }
+
+    pub fn regex(&self) -> Result<&Regex> {
+        if let Some(regex) = self.regex.get() {
+            return Ok(regex);
+        }
+        if self.punctuation.contains(';') {
+            return Err(anyhow!(
+                "Can not use ';' as text punctuation mark, as lines starting with it are comments"
+            ));
+        }
+        let regex = Regex::new(&format!(
+            "^[0-9\\s\\[\\]\\\\{}{}]+$",
+            self.scripts
+                .iter()
+                .map(|script| format!("\\p{{Script={script}}}"))
+                .collect::<String>(),
+            regex::escape(&self.punctuation)
+        ))
+        .with_context(|| {
+            format!(
+                "Can not compile regular expression for text validation with scripts {:?} and \
+                 punctuation {:?}",
+                self.scripts, self.punctuation
+            )
+        })?;
+        Ok(self.regex.get_or_init(|| regex))
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct RawText(pub String);
 
 impl RawText {
-    pub fn validated(&self) -> Result<&Self> {
-        static RAW_REGEX: std::sync::OnceLock<Regex> = std::sync::OnceLock::new();
-        let sentence_regex = RAW_REGEX.get_or_init(|| {
-            Regex::new(r#"^[0-9\p{Script=Cyrillic}\p{Script=Latin}\s,\-\:\."']+$"#)
-                .with_context(|| "Can not compile regular expression for text validation")
-                .unwrap()
-        });
-        if sentence_regex.is_match(&self.0) {
+    pub fn escaped(&self) -> String {
+        self.0
+            .replace('\\', "\\\\")
+            .replace('[', "\\[")
+            .replace(']', "\\]")
+    }
+
+    pub fn validated(&self, charset: &TextCharset) -> Result<&Self> {
+        if charset.regex()?.is_match(&self.0) {
             Ok(self)
         } else {
             Err(anyhow!(
-                "Text part around references must be Cyrillic/Latin text: letters, whitespaces, \
-                 punctuation ,-:.'\", so {:?} does not seem to be text",
+                "Text part around references must consist of digits, whitespaces including line \
+                 breaks, escaped square brackets and backslashes, letters of scripts {:?} and \
+                 punctuation {:?}, so {:?} does not seem to be text",
+                charset.scripts,
+                charset.punctuation,
                 self.0
             ))
         }
     }
 }
 
+pub enum TextPart<'a> {
+    Raw(&'a RawText),
+    Reference(&'a ObjectId, Option<&'a RawText>),
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Text {
     #[serde(default)]
@@ -38,37 +104,95 @@ pub struct Text {
     #[serde(default)]
     pub references: Vec<ObjectId>,
     pub start_with_reference: bool,
+    // references display labels, empty if there are no labelled references at all
+    #[serde(default)]
+    pub labels: Vec<Option<RawText>>,
 }
 
 impl<'a> Text {
-    pub fn new(input: &str, aliases_resolver: &mut AliasesResolver) -> Result<Self> {
+    pub(crate) fn reference_in_text_regex() -> &'static Regex {
         static REFERENCE_IN_TEXT_REGEX: std::sync::OnceLock<Regex> = std::sync::OnceLock::new();
-        let reference_in_text_regex = REFERENCE_IN_TEXT_REGEX.get_or_init(|| {
-            Regex::new(r#"\[(:?([A-Za-z0-9-_]{22})|([^\[\]]+))\]"#)
+        REFERENCE_IN_TEXT_REGEX.get_or_init(|| {
+            Regex::new(r#"\\([\\\[\]])|\[(:?([A-Za-z0-9-_]{22})|([^\[\]|]+))(?:\|([^\[\]]+))?\]"#)
                 .with_context(|| {
                     "Can not compile regular expression to split text on raw text parts and \
                      references"
                 })
                 .unwrap()
+        })
+    }
+
+    pub fn referenced_aliases(input: &str) -> Vec<Alias> {
+        Self::reference_in_text_regex()
+            .captures_iter(input)
+            .filter_map(
+                |reference_match| match Reference::new(reference_match.get(4)?.as_str()) {
+                    Ok(Reference::Alias(alias)) => Some(alias),
+                    _ => None,
+                },
+            )
+            .collect()
+    }
+
+    // canonical form makes visually identical texts have the same id: Unicode NFC, whitespaces
+    // runs collapsed into one line break if they contain one or into one space otherwise, no
+    // whitespaces at start and end
+    pub fn canonical(input: &str) -> String {
+        static WHITESPACES_REGEX: std::sync::OnceLock<Regex> = std::sync::OnceLock::new();
+        let whitespaces_regex = WHITESPACES_REGEX.get_or_init(|| {
+            Regex::new(r#"\s+"#)
+                .with_context(|| "Can not compile regular expression for whitespaces collapsing")
+                .unwrap()
         });
+        let normalized = input.nfc().collect::<String>();
+        whitespaces_regex
+            .replace_all(normalized.trim(), |captures: &regex::Captures| {
+                if captures[0].contains('\n') {
+                    "\n"
+                } else {
+                    " "
+                }
+            })
+            .to_string()
+    }
+
+    pub fn new(input: &str, aliases_resolver: &mut AliasesResolver) -> Result<Self> {
+        Self::parse(&Self::canonical(input), Some(aliases_resolver))
+    }
+
+    pub fn canonicalized(&self) -> Result<Self> {
+        Self::parse(&Self::canonical(&self.composed()), None)
+    }
 
+    fn parse(input: &str, aliases_resolver: Option<&AliasesResolver>) -> Result<Self> {
+        let reference_in_text_regex = Self::reference_in_text_regex();
         let mut result = Self {
             raw_text_parts: Vec::new(),
             references: Vec::new(),
             start_with_reference: false,
+            labels: Vec::new(),
         };
+        let mut raw_text = String::new();
         let mut last_match_end = 0;
         for reference_match in reference_in_text_regex.captures_iter(input) {
             let full_reference_match = reference_match.get(0).unwrap();
-            if full_reference_match.start() == 0 {
-                result.start_with_reference = true;
+            raw_text.push_str(&input[last_match_end..full_reference_match.start()]);
+            last_match_end = full_reference_match.end();
+            if let Some(escaped_match) = reference_match.get(1) {
+                raw_text.push_str(escaped_match.as_str());
+                continue;
             }
-            let text_before = &input[last_match_end..full_reference_match.start()];
-            if !text_before.is_empty() {
-                result.raw_text_parts.push(RawText(text_before.to_string()));
+            if raw_text.is_empty() {
+                if result.references.is_empty() && result.raw_text_parts.is_empty() {
+                    result.start_with_reference = true;
+                }
+            } else {
+                result
+                    .raw_text_parts
+                    .push(RawText(std::mem::take(&mut raw_text)));
             }
             if let Some(thesis_id_string) = reference_match
-                .get(2)
+                .get(3)
                 .map(|thesis_id_string_match| thesis_id_string_match.as_str())
             {
                 result.references.push(
@@ -76,63 +200,92 @@ impl<'a> Text {
                         .unwrap(),
                 );
             } else if let Some(alias_string) = reference_match
-                .get(3)
+                .get(4)
                 .map(|alias_string_match| alias_string_match.as_str())
             {
                 result.references.push(
                     aliases_resolver
-                        .get_thesis_id_by_reference(&Reference::Alias(Alias(
-                            alias_string.to_string(),
-                        )))
+                        .ok_or_else(|| anyhow!("There is no aliases resolver to resolve aliases"))
+                        .and_then(|aliases_resolver| {
+                            aliases_resolver
+                                .get_thesis_id_by_reference(&Reference::new(alias_string)?)
+                        })
                         .with_context(|| {
                             anyhow!(
                                 "Can not parse text {:?} with alias {:?} because do not know such \
-                                 alias",
+                                 alias, use \\[ and \\] to write square brackets literally",
                                 input,
                                 alias_string
                             )
                         })?,
                 );
             }
-            last_match_end = full_reference_match.end();
+            result.labels.push(
+                reference_match
+                    .get(5)
+                    .map(|label_match| RawText(label_match.as_str().to_string())),
+            );
         }
-        if last_match_end < input.len() {
-            let remaining = &input[last_match_end..];
-            if !remaining.is_empty() {
-                result.raw_text_parts.push(RawText(remaining.to_string()));
-            }
+        raw_text.push_str(&input[last_match_end..]);
+        if !raw_text.is_empty() {
+            result.raw_text_parts.push(RawText(raw_text));
+        }
+        if result.labels.iter().all(|label| label.is_none()) {
+            result.labels.clear();
         }
 
         Ok(result)
     }
 
-    pub fn composed(&self) -> String {
-        let mut result_list = Vec::new();
+    pub fn parts(&self) -> Vec<TextPart<'_>> {
+        let mut result = Vec::with_capacity(self.raw_text_parts.len() + self.references.len());
         if self.start_with_reference {
             for (reference_index, reference) in self.references.iter().enumerate() {
-                result_list.push(format!(
-                    "[{}]",
-                    serde_json::to_value(reference).unwrap().as_str().unwrap()
-                ));
+                result.push(TextPart::Reference(reference, self.label(reference_index)));
                 if reference_index < self.raw_text_parts.len() {
-                    result_list.push(self.raw_text_parts[reference_index].0.clone());
+                    result.push(TextPart::Raw(&self.raw_text_parts[reference_index]));
                 }
             }
         } else {
             for (part_index, part) in self.raw_text_parts.iter().enumerate() {
-                result_list.push(part.0.clone());
+                result.push(TextPart::Raw(part));
                 if part_index < self.references.len() {
-                    result_list.push(format!(
-                        "[{}]",
-                        serde_json::to_value(&self.references[part_index])
-                            .unwrap()
-                            .as_str()
-                            .unwrap()
+                    result.push(TextPart::Reference(
+                        &self.references[part_index],
+                        self.label(part_index),
                     ));
                 }
             }
         }
-        result_list.concat()
+        result
+    }
+
+    pub fn label(&self, reference_index: usize) -> Option<&RawText> {
+        self.labels
+            .get(reference_index)
+            .and_then(|label| label.as_ref())
+    }
+
+    fn labelled(reference: &str, label: Option<&RawText>) -> String {
+        if let Some(label) = label {
+            format!("[{reference}|{}]", label.0)
+        } else {
+            format!("[{reference}]")
+        }
+    }
+
+    pub fn composed(&self) -> String {
+        self.parts()
+            .iter()
+            .map(|part| match part {
+                TextPart::Raw(raw_text) => raw_text.escaped(),
+                TextPart::Reference(reference, label) => Self::labelled(
+                    serde_json::to_value(reference).unwrap().as_str().unwrap(),
+                    *label,
+                ),
+            })
+            .collect::<Vec<_>>()
+            .concat()
     }
 
     pub fn composed_with_aliases(
@@ -140,43 +293,54 @@ impl<'a> Text {
         read_able_transaction: &dyn ReadTransactionMethods<'a>,
     ) -> Result<String> {
         let mut result_list = Vec::new();
-        if self.start_with_reference {
-            for (reference_index, reference) in self.references.iter().enumerate() {
-                result_list.push(format!(
-                    "[{}]",
-                    if let Some(alias) = read_able_transaction.get_alias_by_thesis_id(reference)? {
+        for part in self.parts() {
+            result_list.push(match part {
+                TextPart::Raw(raw_text) => raw_text.escaped(),
+                TextPart::Reference(reference, label) => Self::labelled(
+                    &if let Some(alias) = read_able_transaction.get_alias_by_thesis_id(reference)? {
                         alias.0
                     } else {
                         reference.to_string()
-                    }
-                ));
-                if reference_index < self.raw_text_parts.len() {
-                    result_list.push(self.raw_text_parts[reference_index].0.clone());
-                }
-            }
-        } else {
-            for (part_index, part) in self.raw_text_parts.iter().enumerate() {
-                result_list.push(part.0.clone());
-                if part_index < self.references.len() {
-                    result_list.push(format!(
-                        "[{}]",
-                        if let Some(alias) = read_able_transaction
-                            .get_alias_by_thesis_id(&self.references[part_index])?
-                        {
-                            alias.0
-                        } else {
-                            self.references[part_index].to_string()
-                        }
-                    ));
-                }
-            }
+                    },
+                    label,
+                ),
+            });
         }
         Ok(result_list.concat())
     }
 
-    pub fn validated(&self) -> Result<&Self> {
+    // for display only, as names may be id prefixes which become ambiguous later
+    pub fn composed_with_names(&self, names: &BTreeMap<ObjectId, String>) -> String {
+        self.parts()
+            .iter()
+            .map(|part| match part {
+                TextPart::Raw(raw_text) => raw_text.escaped(),
+                TextPart::Reference(reference, label) => Self::labelled(
+                    &names
+                        .get(*reference)
+                        .cloned()
+                        .unwrap_or_else(|| reference.to_string()),
+                    *label,
+                ),
+            })
+            .collect::<Vec<_>>()
+            .concat()
+    }
+
+    pub fn validated(&self, charset: &TextCharset) -> Result<&Self> {
         for part in self.raw_text_parts.iter() {
-            part.validated()?;
+            part.validated(charset)?;
+        }
+        if !self.labels.is_empty() && self.labels.len() != self.references.len() {
+            return Err(anyhow!(
+                "Text must have either no labels or label option for each of it's {} references, \
+                 but it has {} labels options",
+                self.references.len(),
+                self.labels.len()
+            ));
+        }
+        for label in self.labels.iter().flatten() {
+            label.validated(charset)?;
         }
         Ok(self)
     }
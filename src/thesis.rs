@@ -4,9 +4,10 @@ use trove::ObjectId;
 
 use crate::alias::Alias;
 use crate::content::Content;
+use crate::id_scheme::IdScheme;
 use crate::relation::Relation;
 use crate::tag::Tag;
-use crate::text::Text;
+use crate::text::{Text, TextCharset};
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Thesis {
@@ -18,15 +19,15 @@ pub struct Thesis {
 }
 
 impl Thesis {
-    pub fn id(&self) -> Result<ObjectId> {
-        self.content.id()
+    pub fn id_with(&self, id_scheme: &IdScheme) -> Result<ObjectId> {
+        self.content.id_with(id_scheme)
     }
 
-    pub fn validated(&self) -> Result<&Self> {
+    pub fn validated(&self, charset: &TextCharset) -> Result<&Self> {
         if let Some(ref alias) = self.alias {
             alias.validated()?;
         }
-        self.content.validated()?;
+        self.content.validated(charset)?;
         for tag in self.tags.iter() {
             tag.validated()?;
         }
@@ -39,6 +40,7 @@ impl Thesis {
                 raw_text_parts: _,
                 ref references,
                 start_with_reference: _,
+                labels: _,
             }) => references.clone(),
             Content::Relation(Relation {
                 ref from,
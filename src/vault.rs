@@ -0,0 +1,437 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Error, Result};
+use fallible_iterator::FallibleIterator;
+use regex::Regex;
+use trove::ObjectId;
+
+use crate::alias::Alias;
+use crate::aliases_resolver::AliasesResolver;
+use crate::commands::{Command, Reference};
+use crate::content::Content;
+use crate::read_transaction::ReadTransactionMethods;
+use crate::relation::{Relation, RelationKind};
+use crate::tag::Tag;
+use crate::text::{RawText, Text, TextPart};
+use crate::thesis::Thesis;
+
+pub struct Note {
+    pub name: String,
+    pub content: String,
+}
+
+pub fn note_name(thesis_id: &ObjectId, alias: Option<&Alias>) -> String {
+    if let Some(alias) = alias {
+        alias
+            .0
+            .chars()
+            .map(|character| {
+                if "\\/:*?\"<>|#^[]".contains(character) {
+                    '_'
+                } else {
+                    character
+                }
+            })
+            .collect()
+    } else {
+        thesis_id.to_string()
+    }
+}
+
+// names of notes for all theses; distinct aliases may give the same note name, e.g. "a/b" and
+// "a_b", or names differing only in case, which are the same on some file systems, so such names
+// are disambiguated with thesis id
+pub fn notes_names<'a>(
+    read_able_transaction: &dyn ReadTransactionMethods<'a>,
+) -> Result<BTreeMap<ObjectId, String>> {
+    let mut result = BTreeMap::new();
+    let mut theses_ids_by_name: BTreeMap<String, Vec<ObjectId>> = BTreeMap::new();
+    let mut theses = read_able_transaction.iter_theses()?;
+    while let Some(thesis) = theses.next()? {
+        let thesis_id = read_able_transaction.thesis_id(&thesis)?;
+        let name = note_name(&thesis_id, thesis.alias.as_ref());
+        theses_ids_by_name
+            .entry(name.to_lowercase())
+            .or_default()
+            .push(thesis_id.clone());
+        result.insert(thesis_id, name);
+    }
+    for theses_ids in theses_ids_by_name.into_values() {
+        if theses_ids.len() > 1 {
+            for thesis_id in theses_ids {
+                let name = result.get_mut(&thesis_id).unwrap();
+                if *name != thesis_id.to_string() {
+                    *name = format!("{name} {thesis_id}");
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+pub struct VaultExporter<'a> {
+    pub read_able_transaction: &'a dyn ReadTransactionMethods<'a>,
+    pub theses_iterator: Box<dyn FallibleIterator<Item = Thesis, Error = Error> + 'a>,
+    pub notes_names: BTreeMap<ObjectId, String>,
+}
+
+impl<'a> VaultExporter<'a> {
+    pub fn new(read_able_transaction: &'a dyn ReadTransactionMethods<'a>) -> Result<Self> {
+        Ok(Self {
+            read_able_transaction,
+            theses_iterator: Box::new(read_able_transaction.iter_theses()?),
+            notes_names: notes_names(read_able_transaction)?,
+        })
+    }
+
+    fn note_name(&self, thesis_id: &ObjectId) -> Result<&str> {
+        self.notes_names
+            .get(thesis_id)
+            .map(|name| name.as_str())
+            .ok_or_else(|| anyhow!("Can not find note name for thesis {thesis_id:?}"))
+    }
+
+    pub fn write(mut self, directory: &Path) -> Result<()> {
+        std::fs::create_dir_all(directory)
+            .with_context(|| format!("Can not create vault directory {directory:?}"))?;
+        while let Some(note) = self.next()? {
+            let note_path = directory.join(format!("{}.md", note.name));
+            std::fs::write(&note_path, note.content)
+                .with_context(|| format!("Can not write vault note {note_path:?}"))?;
+        }
+        Ok(())
+    }
+
+    fn wikilink(&self, thesis_id: &ObjectId, label: Option<&RawText>) -> Result<String> {
+        let name = self.note_name(thesis_id)?;
+        Ok(if let Some(label) = label {
+            format!("[[{name}|{}]]", label.0)
+        } else {
+            format!("[[{name}]]")
+        })
+    }
+}
+
+impl<'a> FallibleIterator for VaultExporter<'a> {
+    type Item = Note;
+    type Error = Error;
+
+    fn next(&mut self) -> Result<Option<Self::Item>> {
+        Ok(if let Some(thesis) = self.theses_iterator.next()? {
+            let thesis_id = self.read_able_transaction.thesis_id(&thesis)?;
+            let mut front_matter = vec![format!("id: {thesis_id}")];
+            if let Some(ref alias) = thesis.alias {
+                front_matter.push(format!("alias: {}", serde_json::to_string(&alias.0)?));
+            }
+            let mut body = String::new();
+            match thesis.content {
+                Content::Text(ref text) => {
+                    for part in text.parts() {
+                        match part {
+                            TextPart::Raw(raw_text) => body.push_str(&raw_text.escaped()),
+                            TextPart::Reference(reference, label) => {
+                                body.push_str(&self.wikilink(reference, label)?)
+                            }
+                        }
+                    }
+                    body.push('\n');
+                }
+                Content::Relation(ref relation) => {
+                    front_matter.push(format!(
+                        "from: {}",
+                        serde_json::to_string(&self.wikilink(&relation.from, None)?)?
+                    ));
+                    front_matter.push(format!(
+                        "kind: {}",
+                        serde_json::to_string(
+                            self.read_able_transaction
+                                .sweater_config()
+                                .exported_relation_kind(&relation.kind)
+                        )?
+                    ));
+                    front_matter.push(format!(
+                        "to: {}",
+                        serde_json::to_string(&self.wikilink(&relation.to, None)?)?
+                    ));
+                }
+            }
+            // tags are the last paragraph of body, so they are told from text, which has no
+            // empty lines when canonical
+            if !thesis.tags.is_empty() {
+                if !body.is_empty() {
+                    body.push('\n');
+                }
+                body.push_str(
+                    &thesis
+                        .tags
+                        .iter()
+                        .map(|tag| format!("#{}", tag.0))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                );
+                body.push('\n');
+            }
+            Some(Note {
+                name: self.note_name(&thesis_id)?.to_string(),
+                content: format!("---\n{}\n---\n{body}", front_matter.join("\n")),
+            })
+        } else {
+            None
+        })
+    }
+}
+
+struct ParsedNote {
+    alias: Option<Alias>,
+    text: Option<String>,
+    relation: Option<(String, RelationKind, String)>,
+    tags: Vec<Tag>,
+}
+
+pub struct VaultImporter<'a, 'b> {
+    pub aliases_resolver: &'b mut AliasesResolver<'a>,
+}
+
+impl<'a, 'b> VaultImporter<'a, 'b> {
+    pub fn new(aliases_resolver: &'b mut AliasesResolver<'a>) -> Self {
+        Self { aliases_resolver }
+    }
+
+    fn wikilink_regex() -> &'static Regex {
+        static WIKILINK_REGEX: std::sync::OnceLock<Regex> = std::sync::OnceLock::new();
+        WIKILINK_REGEX.get_or_init(|| {
+            Regex::new(r#"\[\[([^\[\]|]+)(?:\|([^\[\]]+))?\]\]"#)
+                .with_context(|| "Can not compile regular expression for wikilinks")
+                .unwrap()
+        })
+    }
+
+    fn parse_note(name: &str, content: &str) -> Result<ParsedNote> {
+        let content = content.replace("\r\n", "\n");
+        let (front_matter, body) = content
+            .strip_prefix("---\n")
+            .and_then(|rest| rest.split_once("\n---\n").or(rest.split_once("\n---")))
+            .unwrap_or(("", &content));
+        let mut fields = BTreeMap::new();
+        for line in front_matter.lines() {
+            if let Some((key, value)) = line.split_once(':') {
+                let value = value.trim();
+                fields.insert(
+                    key.trim().to_string(),
+                    if value.starts_with('"') {
+                        serde_json::from_str(value).with_context(|| {
+                            format!("Can not parse front matter value {value:?} in note {name:?}")
+                        })?
+                    } else {
+                        value.to_string()
+                    },
+                );
+            }
+        }
+
+        let relation = match (fields.get("from"), fields.get("kind"), fields.get("to")) {
+            (Some(from), Some(kind), Some(to)) => {
+                Some((from.clone(), RelationKind(kind.clone()), to.clone()))
+            }
+            (None, None, None) => None,
+            _ => {
+                return Err(anyhow!(
+                    "Can not parse note {name:?}: relation note must have all of from, kind and \
+                     to front matter fields"
+                ));
+            }
+        };
+        // tags paragraph is delimited from text with empty line, while relation note body may
+        // consist of it alone
+        let body = body.trim();
+        let (text, tags_paragraph) = match body.rsplit_once("\n\n") {
+            Some((text, last_paragraph)) => (text.trim_end(), last_paragraph),
+            None if relation.is_some() => ("", body),
+            None => (body, ""),
+        };
+        let (text, tags) = match tags_paragraph
+            .split_whitespace()
+            .map(|word| {
+                word.strip_prefix('#')
+                    .map(|tag| Tag(tag.to_string()))
+                    .filter(|tag| tag.validated().is_ok())
+            })
+            .collect::<Option<Vec<_>>>()
+        {
+            Some(tags) => (text.to_string(), tags),
+            None => (body.to_string(), Vec::new()),
+        };
+        if relation.is_some() && !text.is_empty() {
+            return Err(anyhow!(
+                "Can not parse note {name:?}: relation note must not have text"
+            ));
+        }
+        Ok(ParsedNote {
+            alias: fields.get("alias").map(|alias| Alias(alias.clone())),
+            text: if relation.is_none() { Some(text) } else { None },
+            relation,
+            tags,
+        })
+    }
+
+    fn dependencies(note: &ParsedNote) -> Vec<String> {
+        let mut result = Vec::new();
+        if let Some(ref text) = note.text {
+            for captures in Self::wikilink_regex().captures_iter(text) {
+                result.push(captures[1].to_string());
+            }
+        }
+        if let Some((ref from, _, ref to)) = note.relation {
+            for link in [from, to] {
+                if let Some(captures) = Self::wikilink_regex().captures(link) {
+                    result.push(captures[1].to_string());
+                }
+            }
+        }
+        result
+    }
+
+    fn sorted_names(notes: &BTreeMap<String, ParsedNote>) -> Result<Vec<String>> {
+        // 1 is being visited, 2 is visited
+        let mut states: BTreeMap<&str, u8> = BTreeMap::new();
+        let mut result = Vec::with_capacity(notes.len());
+        for root in notes.keys() {
+            let mut stack = vec![(root.as_str(), false)];
+            while let Some((name, dependencies_visited)) = stack.pop() {
+                if dependencies_visited {
+                    states.insert(name, 2);
+                    result.push(name.to_string());
+                    continue;
+                }
+                match states.get(name) {
+                    Some(2) => continue,
+                    Some(_) => {
+                        return Err(anyhow!(
+                            "Can not import vault: note {name:?} depends on itself through links"
+                        ));
+                    }
+                    None => {}
+                }
+                states.insert(name, 1);
+                stack.push((name, true));
+                for dependency in Self::dependencies(&notes[name]) {
+                    if let Some((dependency_name, _)) = notes.get_key_value(&dependency) {
+                        match states.get(dependency_name.as_str()) {
+                            Some(1) => {
+                                return Err(anyhow!(
+                                    "Can not import vault: notes {name:?} and {dependency:?} \
+                                     depend on each other through links"
+                                ));
+                            }
+                            Some(_) => {}
+                            None => stack.push((dependency_name.as_str(), false)),
+                        }
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    fn resolve(&self, name: &str, imported: &BTreeMap<String, ObjectId>) -> Result<ObjectId> {
+        if let Some(thesis_id) = imported.get(name) {
+            Ok(thesis_id.clone())
+        } else {
+            self.aliases_resolver
+                .get_thesis_id_by_reference(&Reference::new(name)?)
+        }
+    }
+
+    pub fn commands(&mut self, directory: &Path) -> Result<Vec<Command>> {
+        let mut notes = BTreeMap::new();
+        for entry in std::fs::read_dir(directory)
+            .with_context(|| format!("Can not read vault directory {directory:?}"))?
+        {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "md")
+                && let Some(name) = path.file_stem().and_then(|stem| stem.to_str())
+            {
+                let content = std::fs::read_to_string(&path)
+                    .with_context(|| format!("Can not read vault note {path:?}"))?;
+                notes.insert(name.to_string(), Self::parse_note(name, &content)?);
+            }
+        }
+
+        let mut imported: BTreeMap<String, ObjectId> = BTreeMap::new();
+        let mut result = Vec::with_capacity(notes.len());
+        for name in Self::sorted_names(&notes)? {
+            let note = &notes[&name];
+            let content = if let Some((ref from, ref kind, ref to)) = note.relation {
+                let mut endpoints = Vec::with_capacity(2);
+                for link in [from, to] {
+                    let target = Self::wikilink_regex()
+                        .captures(link)
+                        .map(|captures| captures[1].to_string())
+                        .unwrap_or(link.clone());
+                    endpoints.push(self.resolve(&target, &imported).with_context(|| {
+                        format!("Can not resolve relation endpoint {link:?} in note {name:?}")
+                    })?);
+                }
+                Content::Relation(Relation {
+                    from: endpoints[0].clone(),
+                    kind: self
+                        .aliases_resolver
+                        .read_able_transaction
+                        .sweater_config()
+                        .relation_kind(&kind.0)
+                        .unwrap_or(kind.clone()),
+                    to: endpoints[1].clone(),
+                })
+            } else {
+                let text = note.text.clone().unwrap_or_default();
+                let mut dsl_text = String::with_capacity(text.len());
+                let mut last_match_end = 0;
+                for captures in Self::wikilink_regex().captures_iter(&text) {
+                    let full_match = captures.get(0).unwrap();
+                    dsl_text.push_str(&text[last_match_end..full_match.start()]);
+                    let thesis_id = self.resolve(&captures[1], &imported).with_context(|| {
+                        format!(
+                            "Can not resolve link {:?} in note {name:?}",
+                            full_match.as_str()
+                        )
+                    })?;
+                    if let Some(label_match) = captures.get(2) {
+                        dsl_text.push_str(&format!("[{thesis_id}|{}]", label_match.as_str()));
+                    } else {
+                        dsl_text.push_str(&format!("[{thesis_id}]"));
+                    }
+                    last_match_end = full_match.end();
+                }
+                dsl_text.push_str(&text[last_match_end..]);
+                Content::Text(Text::new(&dsl_text, self.aliases_resolver)?)
+            };
+            let thesis = Thesis {
+                alias: note.alias.clone(),
+                content,
+                tags: note.tags.clone(),
+            };
+            let thesis_id = self
+                .aliases_resolver
+                .read_able_transaction
+                .thesis_id(&thesis)?;
+            if let Some(ref alias) = thesis.alias {
+                self.aliases_resolver
+                    .remember(alias.clone(), thesis_id.clone());
+            }
+            imported.insert(name.clone(), thesis_id);
+            let command = Command::AddThesis(thesis);
+            command
+                .validated(
+                    &self
+                        .aliases_resolver
+                        .read_able_transaction
+                        .sweater_config()
+                        .text_charset,
+                )
+                .with_context(|| format!("Invalid thesis imported from note {name:?}"))?;
+            result.push(command);
+        }
+        Ok(result)
+    }
+}
@@ -1,4 +1,4 @@
-use anyhow::{anyhow, Error, Result};
+use anyhow::{anyhow, Context, Error, Result};
 use fallible_iterator::FallibleIterator;
 use trove::{path_segments, IndexRecordType, Object, ObjectId};
 
@@ -12,6 +12,16 @@ use crate::sweater::SweaterConfig;
 use crate::tag::Tag;
 use crate::thesis::Thesis;
 
+// in idempotent mode adding existing thesis merges it's tags and updates it's alias, and removing
+// missing thesis, referenced by id or by alias nobody has, is no-op, so re-applying the same
+// commands is safe; in strict mode both are errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ApplyMode {
+    #[default]
+    Strict,
+    Idempotent,
+}
+
 pub struct WriteTransaction<'a, 'b, 'c, 'd> {
     pub chest_transaction: &'a mut trove::WriteTransaction<'b, 'c, 'd>,
     pub sweater_config: SweaterConfig,
@@ -27,7 +37,7 @@ impl<'a, 'b, 'c, 'd> ReadTransactionMethods<'a> for &mut WriteTransaction<'a, 'b
 
 impl WriteTransaction<'_, '_, '_, '_> {
     pub fn insert_thesis(&mut self, thesis: Thesis) -> Result<()> {
-        let thesis_id = thesis.id()?;
+        let thesis_id = self.thesis_id(&thesis)?;
         if self.chest_transaction.contains_object_with_id(&thesis_id)? {
             Err(anyhow!(
                 "Can not insert thesis {thesis:?} with id {thesis_id:?} as chest already contains \
@@ -72,6 +82,31 @@ impl WriteTransaction<'_, '_, '_, '_> {
         }
     }
 
+    // adds thesis or, if it already exists, adds it's tags and sets it's alias if given
+    pub fn upsert_thesis(&mut self, thesis: Thesis) -> Result<()> {
+        let thesis_id = self.thesis_id(&thesis)?;
+        if let Some(ref alias) = thesis.alias
+            && let Some(other_thesis_id) = self.get_thesis_id_by_alias(alias)?
+            && other_thesis_id != thesis_id
+        {
+            return Err(anyhow!(
+                "Can not set alias {alias:?} of thesis {thesis_id:?} as it is already alias of \
+                 thesis {other_thesis_id:?}"
+            ));
+        }
+        if self.chest_transaction.contains_object_with_id(&thesis_id)? {
+            for tag in thesis.tags {
+                self.tag_thesis(&thesis_id, tag)?;
+            }
+            if let Some(alias) = thesis.alias {
+                self.set_alias(thesis_id, alias)?;
+            }
+            Ok(())
+        } else {
+            self.insert_thesis(thesis)
+        }
+    }
+
     pub fn tag_thesis(&mut self, thesis_id: &ObjectId, tag: Tag) -> Result<()> {
         if !self.chest_transaction.contains_element(
             thesis_id,
@@ -144,9 +179,42 @@ impl WriteTransaction<'_, '_, '_, '_> {
         Ok(())
     }
 
+    // moves every alias in old prefix subtree under new prefix, e.g. with prefixes "(R-r)" and
+    // "(Q-q)" alias "(R-r).d" becomes "(Q-q).d", while "(R-r)x" is left as it is
+    pub fn rename_aliases_subtree(&mut self, old_prefix: &str, new_prefix: &str) -> Result<()> {
+        let subtree = self.get_aliases_by_prefix(old_prefix)?;
+        let mut renamed = Vec::with_capacity(subtree.len());
+        for (old_alias, thesis_id) in subtree.iter() {
+            let new_alias = Alias(format!("{new_prefix}{}", &old_alias.0[old_prefix.len()..]));
+            new_alias
+                .validated()
+                .with_context(|| format!("Can not rename alias {old_alias:?} to {new_alias:?}"))?;
+            if let Some(other_thesis_id) = self.get_thesis_id_by_alias(&new_alias)?
+                && !subtree.iter().any(|(_, id)| *id == other_thesis_id)
+            {
+                return Err(anyhow!(
+                    "Can not rename alias {old_alias:?} to {new_alias:?} as it is already alias \
+                     of thesis {other_thesis_id:?} outside of renamed subtree"
+                ));
+            }
+            renamed.push((thesis_id.clone(), new_alias));
+        }
+        for (thesis_id, new_alias) in renamed {
+            self.set_alias(thesis_id, new_alias)?;
+        }
+        Ok(())
+    }
+
     pub fn execute_command(&mut self, command: &Command) -> Result<&Self> {
+        self.execute_command_in_mode(command, ApplyMode::Strict)
+    }
+
+    pub fn execute_command_in_mode(&mut self, command: &Command, mode: ApplyMode) -> Result<&Self> {
         match command {
-            Command::AddThesis(thesis) => self.insert_thesis(thesis.clone())?,
+            Command::AddThesis(thesis) => match mode {
+                ApplyMode::Strict => self.insert_thesis(thesis.clone())?,
+                ApplyMode::Idempotent => self.upsert_thesis(thesis.clone())?,
+            },
             Command::RemoveThesis(thesis_id) => self.remove_thesis(thesis_id)?,
             Command::AddTags(thesis_id, tags) => {
                 for tag in tags {